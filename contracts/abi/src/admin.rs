@@ -0,0 +1,264 @@
+//! Admin role shared by the factory and the spawn handler.
+//!
+//! The role is held by a set of owners, the signers of admin operations, any
+//! of whom may perform them. Handing the role to a new set takes two steps:
+//! `threshold` current owners propose the same set, then one of its owners
+//! accepts it.
+
+use linera_sdk::base::AccountOwner;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AdminSet {
+    pub owners: Vec<AccountOwner>,
+    /// Owners who must propose a handover before it can be accepted.
+    pub threshold: u32,
+}
+
+/// A proposed handover of the role.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AdminTransfer {
+    pub proposed: AdminSet,
+    pub approvals: Vec<AccountOwner>, // Current owners who proposed it
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct AdminRole {
+    pub current: Option<AdminSet>, // None until initialized
+    pub pending: Option<AdminTransfer>,
+    /// Signer who instantiated the application, the only one allowed to
+    /// initialize the role with an operation.
+    pub instantiated_by: Option<AccountOwner>,
+}
+
+#[derive(Debug, Error)]
+pub enum AdminError {
+    #[error("Unauthorized")]
+    Unauthorized,
+    #[error("Admin already initialized")]
+    AlreadyInitialized,
+    #[error("Invalid admin set")]
+    InvalidAdminSet,
+    #[error("No admin transfer ready to accept")]
+    NoTransferReady,
+}
+
+impl AdminSet {
+    pub fn single(owner: AccountOwner) -> Self {
+        AdminSet {
+            owners: vec![owner],
+            threshold: 1,
+        }
+    }
+
+    /// Checks that the owners are distinct and the threshold is reachable.
+    pub fn validate(&self) -> Result<(), AdminError> {
+        let repeated = self
+            .owners
+            .iter()
+            .enumerate()
+            .any(|(index, owner)| self.owners[..index].contains(owner));
+        if repeated || self.threshold == 0 || self.threshold as usize > self.owners.len() {
+            return Err(AdminError::InvalidAdminSet);
+        }
+        Ok(())
+    }
+}
+
+impl AdminRole {
+    pub fn is_admin(&self, owner: AccountOwner) -> bool {
+        self.current
+            .as_ref()
+            .is_some_and(|admins| admins.owners.contains(&owner))
+    }
+
+    pub fn initialize(&mut self, admins: AdminSet) -> Result<(), AdminError> {
+        if self.current.is_some() {
+            return Err(AdminError::AlreadyInitialized);
+        }
+        admins.validate()?;
+        self.current = Some(admins);
+        Ok(())
+    }
+
+    /// Initializes the role on behalf of `signer`, which must be the signer
+    /// who instantiated the application.
+    pub fn initialize_as(&mut self, signer: AccountOwner, admins: AdminSet) -> Result<(), AdminError> {
+        if self.instantiated_by != Some(signer) {
+            return Err(AdminError::Unauthorized);
+        }
+        self.initialize(admins)
+    }
+
+    /// Records the proposal of `caller` to hand the role to `proposed`. A
+    /// different proposal replaces the pending one and its approvals.
+    pub fn propose(&mut self, caller: AccountOwner, proposed: AdminSet) -> Result<(), AdminError> {
+        if !self.is_admin(caller) {
+            return Err(AdminError::Unauthorized);
+        }
+        proposed.validate()?;
+        match &mut self.pending {
+            Some(transfer) if transfer.proposed == proposed => {
+                if !transfer.approvals.contains(&caller) {
+                    transfer.approvals.push(caller);
+                }
+            }
+            _ => {
+                self.pending = Some(AdminTransfer {
+                    proposed,
+                    approvals: vec![caller],
+                })
+            }
+        }
+        Ok(())
+    }
+
+    /// Completes the pending handover once enough owners proposed it. Only an
+    /// owner of the proposed set may accept, proving it holds its key.
+    pub fn accept(&mut self, caller: AccountOwner) -> Result<(), AdminError> {
+        let threshold = self.current.as_ref().map_or(1, |admins| admins.threshold);
+        let Some(transfer) = self
+            .pending
+            .as_ref()
+            .filter(|transfer| transfer.approvals.len() >= threshold as usize)
+        else {
+            return Err(AdminError::NoTransferReady);
+        };
+        if !transfer.proposed.owners.contains(&caller) {
+            return Err(AdminError::Unauthorized);
+        }
+        self.current = self.pending.take().map(|transfer| transfer.proposed);
+        Ok(())
+    }
+
+    /// Drops the pending handover; any current owner may do so.
+    pub fn cancel(&mut self, caller: AccountOwner) -> Result<(), AdminError> {
+        if !self.is_admin(caller) {
+            return Err(AdminError::Unauthorized);
+        }
+        self.pending = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn owner(byte: u8) -> AccountOwner {
+        AccountOwner::Address20([byte; 20])
+    }
+
+    fn set(owners: &[u8], threshold: u32) -> AdminSet {
+        AdminSet {
+            owners: owners.iter().copied().map(owner).collect(),
+            threshold,
+        }
+    }
+
+    fn role(owners: &[u8], threshold: u32) -> AdminRole {
+        let mut role = AdminRole::default();
+        role.initialize(set(owners, threshold)).unwrap();
+        role
+    }
+
+    #[test]
+    fn rejects_invalid_sets() {
+        assert!(set(&[1, 2], 2).validate().is_ok());
+        assert!(matches!(set(&[1, 1], 1).validate(), Err(AdminError::InvalidAdminSet)));
+        assert!(matches!(set(&[1], 0).validate(), Err(AdminError::InvalidAdminSet)));
+        assert!(matches!(set(&[1], 2).validate(), Err(AdminError::InvalidAdminSet)));
+        assert!(matches!(set(&[], 1).validate(), Err(AdminError::InvalidAdminSet)));
+    }
+
+    #[test]
+    fn only_the_instantiator_initializes_once() {
+        let mut role = AdminRole {
+            instantiated_by: Some(owner(9)),
+            ..AdminRole::default()
+        };
+        assert!(matches!(
+            role.initialize_as(owner(1), set(&[1], 1)),
+            Err(AdminError::Unauthorized)
+        ));
+        role.initialize_as(owner(9), set(&[1], 1)).unwrap();
+        assert!(role.is_admin(owner(1)));
+        assert!(!role.is_admin(owner(9)));
+        assert!(matches!(
+            role.initialize_as(owner(9), set(&[2], 1)),
+            Err(AdminError::AlreadyInitialized)
+        ));
+    }
+
+    #[test]
+    fn uninstantiated_role_cannot_be_initialized_by_operation() {
+        let mut role = AdminRole::default();
+        assert!(matches!(
+            role.initialize_as(owner(1), set(&[1], 1)),
+            Err(AdminError::Unauthorized)
+        ));
+    }
+
+    #[test]
+    fn single_owner_hands_over_to_another_owner() {
+        let mut role = role(&[1], 1);
+        role.propose(owner(1), set(&[2], 1)).unwrap();
+        assert!(matches!(role.accept(owner(1)), Err(AdminError::Unauthorized)));
+        role.accept(owner(2)).unwrap();
+        assert!(role.is_admin(owner(2)));
+        assert!(!role.is_admin(owner(1)));
+        assert!(role.pending.is_none());
+    }
+
+    #[test]
+    fn handover_needs_threshold_of_distinct_owners() {
+        let mut role = role(&[1, 2, 3], 2);
+        let proposed = set(&[4, 5], 1);
+        role.propose(owner(1), proposed.clone()).unwrap();
+        role.propose(owner(1), proposed.clone()).unwrap();
+        assert_eq!(role.pending.as_ref().unwrap().approvals, vec![owner(1)]);
+        assert!(matches!(role.accept(owner(4)), Err(AdminError::NoTransferReady)));
+
+        role.propose(owner(3), proposed).unwrap();
+        assert!(matches!(role.accept(owner(1)), Err(AdminError::Unauthorized)));
+        role.accept(owner(5)).unwrap();
+        assert_eq!(role.current, Some(set(&[4, 5], 1)));
+    }
+
+    #[test]
+    fn different_proposal_replaces_approvals() {
+        let mut role = role(&[1, 2], 2);
+        role.propose(owner(1), set(&[3], 1)).unwrap();
+        role.propose(owner(2), set(&[4], 1)).unwrap();
+        let pending = role.pending.as_ref().unwrap();
+        assert_eq!(pending.proposed, set(&[4], 1));
+        assert_eq!(pending.approvals, vec![owner(2)]);
+        assert!(matches!(role.accept(owner(4)), Err(AdminError::NoTransferReady)));
+    }
+
+    #[test]
+    fn only_owners_propose_valid_sets() {
+        let mut role = role(&[1], 1);
+        assert!(matches!(
+            role.propose(owner(2), set(&[2], 1)),
+            Err(AdminError::Unauthorized)
+        ));
+        assert!(matches!(
+            role.propose(owner(1), set(&[2], 2)),
+            Err(AdminError::InvalidAdminSet)
+        ));
+        assert!(role.pending.is_none());
+    }
+
+    #[test]
+    fn owners_cancel_pending_handover() {
+        let mut role = role(&[1, 2], 2);
+        role.propose(owner(1), set(&[3], 1)).unwrap();
+        assert!(matches!(role.cancel(owner(3)), Err(AdminError::Unauthorized)));
+        role.cancel(owner(2)).unwrap();
+        assert!(role.pending.is_none());
+        assert!(matches!(role.accept(owner(3)), Err(AdminError::NoTransferReady)));
+        assert!(role.is_admin(owner(1)));
+    }
+}
@@ -0,0 +1,233 @@
+//! ABI and parameters of the Factory contract (SDK 0.15).
+
+use linera_sdk::{
+    abi::ContractAbi,
+    base::{Account, Amount, ApplicationId, ChainId, ModuleId, Timestamp},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::{
+    admin::{AdminRole, AdminSet},
+    market::RoyaltyPayment,
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub enum Operation {
+    /// Only the signer who instantiated the factory may initialize it.
+    Initialize { admin: AdminSet },
+    /// Admin only: proposes handing the admin role to `new_admin`, which
+    /// takes proposals from the threshold of current owners.
+    TransferAdmin { new_admin: AdminSet },
+    /// Called by an owner of the proposed set to complete a handover.
+    AcceptAdmin,
+    /// Admin only: drops a proposed handover.
+    CancelAdminTransfer,
+    /// Creates a market owned by the signer, with a new market application.
+    CreateMarket {
+        question: String,
+        outcomes: Vec<String>,
+        expiry_time: Timestamp,
+        parent_market_id: Option<String>,
+        /// Fee the market takes from every bet, in basis points.
+        #[serde(default)]
+        trading_fee_basis_points: u32,
+    },
+    /// Called by a market application on the factory chain to list itself as
+    /// the root of a new tree. The caller is recorded as the market's
+    /// application and its signer as the creator; of `market_info`, only the
+    /// id, question, outcomes and expiry time are kept. Ids starting with
+    /// [`MARKET_ID_PREFIX`] and any parent are refused.
+    RegisterMarket { market_info: MarketInfo },
+    /// Called by the spawn handler on the factory chain to create a child market,
+    /// or a root market for scheduled spawns. Each `spawn_id` creates one market,
+    /// owned by `creator`, the author of the rule that spawned it.
+    SpawnMarket {
+        spawn_id: String,
+        creator: Account,
+        parent_market_id: Option<String>,
+        question: String,
+        outcomes: Vec<String>,
+        expiry_time: Timestamp,
+        seed_liquidity: Amount,
+        trading_fee_basis_points: u32, // Royalties are paid out of these fees
+        royalties: Vec<RoyaltyShare>,
+    },
+    /// Called by a market application on the factory chain once it resolves.
+    MarkMarketResolved {
+        market_id: String,
+        winning_outcome: String,
+    },
+    /// Looks up a market's creator and its ancestors, with theirs, through the
+    /// parent links.
+    MarketLineage { market_id: String },
+    /// Looks up the royalties a market owes out of its fees.
+    MarketRoyalties { market_id: String },
+    /// Looks up a market's registry entry.
+    MarketDetails { market_id: String },
+    /// Admin only: replaces the limits on the shape of market trees.
+    SetTreeLimits { limits: TreeLimits },
+    /// Called by a market application on the factory chain for each royalty it
+    /// paid, so recipients can look up what they received.
+    RecordRoyalty { payment: RoyaltyPayment },
+}
+
+/// Prefix of the ids the factory gives the markets it creates.
+pub const MARKET_ID_PREFIX: &str = "market_";
+
+/// Typed outcome of an operation, returned to cross-application callers.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Response {
+    Ok,
+    InvalidParameters,
+    Unauthorized,
+    MarketCreated { market_id: String },
+    MarketRegistered { market_id: String },
+    MarketAlreadyExists { market_id: String },
+    MarketResolved { market_id: String },
+    MarketNotFound { market_id: String },
+    TreeLimitExceeded { parent_market_id: String },
+    AlreadySpawned { spawn_id: String, market_id: String },
+    Lineage {
+        creator: Account,
+        ancestors: Vec<(String, Account)>, // With their creators, nearest parent first
+    },
+    Royalties { royalties: Vec<RoyaltyShare> },
+    Market { market_info: Box<MarketInfo> },
+}
+
+/// A market as recorded in the factory's registry.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MarketInfo {
+    pub market_id: String,
+    pub chain_id: ChainId,
+    /// Market application taking the bets, if any.
+    pub application_id: Option<ApplicationId>,
+    pub question: String,
+    pub outcomes: Vec<String>,
+    pub parent_market_id: Option<String>,
+    pub child_markets: Vec<String>,
+    pub depth: u32, // Distance from the root of the market tree
+    pub created_at: Timestamp,
+    pub expiry_time: Timestamp,
+    pub creator: Account, // Paid the parent creator share of the market's children
+    pub resolved: bool,
+    pub winning_outcome: Option<String>,
+    pub seed_liquidity: Amount,
+    pub royalties: Vec<RoyaltyShare>, // Recipients all resolved
+}
+
+/// State of the factory, stored as a single register and read as-is by its
+/// service.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct FactoryStateData {
+    pub markets: HashMap<String, MarketInfo>,
+    pub spawned_markets: HashMap<String, String>, // Spawn id to the market it created
+    pub market_count: u64,
+    pub tree_limits: TreeLimits,
+    pub admin: AdminRole,
+    pub royalty_payments: Vec<RoyaltyPayment>, // Reported by the paying markets
+}
+
+impl FactoryStateData {
+    /// Returns the market `parent_id`, if the tree limits let it gain a child.
+    pub fn parent_for_child(&self, parent_id: &str) -> Result<&MarketInfo, Response> {
+        let Some(parent_market) = self.markets.get(parent_id) else {
+            return Err(Response::MarketNotFound {
+                market_id: parent_id.to_string(),
+            });
+        };
+        let children = parent_market.child_markets.len();
+        if !self.tree_limits.allows_child(parent_market.depth, children) {
+            return Err(Response::TreeLimitExceeded {
+                parent_market_id: parent_id.to_string(),
+            });
+        }
+        Ok(parent_market)
+    }
+
+    /// Adds a market to the registry and links it to its parent.
+    pub fn add_market(&mut self, market_info: MarketInfo) {
+        if let Some(parent_id) = &market_info.parent_market_id {
+            if let Some(parent_market) = self.markets.get_mut(parent_id) {
+                parent_market.child_markets.push(market_info.market_id.clone());
+            }
+        }
+        self.markets.insert(market_info.market_id.clone(), market_info);
+    }
+}
+
+/// Share of a spawned market's fees owed to someone it builds on.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RoyaltyShare {
+    pub role: RoyaltyRole,
+    /// `None` for a parent creator share until the factory fills it in.
+    pub recipient: Option<Account>,
+    pub basis_points: u32, // Of the fees the market collects
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum RoyaltyRole {
+    RuleAuthor,
+    ParentCreator,
+}
+
+/// Whether `royalties` claim no more than all of a market's fees.
+pub fn royalties_fit(royalties: &[RoyaltyShare]) -> bool {
+    royalties
+        .iter()
+        .try_fold(0u32, |total, share| total.checked_add(share.basis_points))
+        .is_some_and(|total| total <= 10_000)
+}
+
+/// Bounds on the shape of a market tree, so spawn chains cannot recurse forever.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TreeLimits {
+    /// Deepest level a market may sit at; roots are at depth 0.
+    pub max_depth: u32,
+    /// Most children a single market may have.
+    pub max_children: u32,
+}
+
+impl Default for TreeLimits {
+    fn default() -> Self {
+        TreeLimits {
+            max_depth: 10,
+            max_children: 20,
+        }
+    }
+}
+
+impl TreeLimits {
+    /// Whether a parent at `parent_depth` with `children` children may gain another.
+    pub fn allows_child(&self, parent_depth: u32, children: usize) -> bool {
+        parent_depth < self.max_depth && children < self.max_children as usize
+    }
+}
+
+/// Argument of a new deployment.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct InstantiationArgument {
+    /// Sets the admin right away, instead of a later `Initialize`.
+    #[serde(default)]
+    pub admin: Option<AdminSet>,
+}
+
+/// Peer applications the factory talks to.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Parameters {
+    /// Module used to instantiate new market applications.
+    pub market_module_id: ModuleId,
+    /// Spawn handler application forwarding spawn requests.
+    pub spawn_handler_id: ApplicationId,
+    /// Chain hosting the spawn handler.
+    pub spawn_handler_chain_id: ChainId,
+}
+
+#[derive(Debug)]
+pub struct FactoryAbi;
+
+impl ContractAbi for FactoryAbi {
+    type Operation = Operation;
+    type Response = Response;
+}
@@ -0,0 +1,8 @@
+//! ABI shared by the Fractal Protocol applications.
+//!
+//! Contracts that call one another depend on this crate rather than on each
+//! other, since every contract crate exports its own Wasm entry points.
+
+pub mod admin;
+pub mod factory;
+pub mod market;
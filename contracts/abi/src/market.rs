@@ -0,0 +1,106 @@
+//! ABI and parameters of the Market contract (SDK 0.15).
+
+use linera_sdk::{
+    abi::ContractAbi,
+    base::{Account, AccountOwner, Amount, ApplicationId, ChainId, Timestamp},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::factory::RoyaltyRole;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub enum Operation {
+    CreateMarket {
+        market_id: String,
+        question: String,
+        outcomes: Vec<String>,
+        expiry_time: Timestamp,
+        /// Fee taken from every bet, in basis points; at most 10_000.
+        #[serde(default)]
+        trading_fee_basis_points: u32,
+    },
+    /// The fee on `amount` is transferred from the signer's account, so bets
+    /// on markets with a fee must be signed.
+    PlaceBet {
+        outcome: String,
+        amount: Amount,
+    },
+    ResolveMarket {
+        winning_outcome: String,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ResponseBytes(pub Vec<u8>);
+
+/// State of a market application, stored as a single register and read as-is
+/// by its service.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct MarketStateData {
+    pub market_id: String,
+    pub question: String,
+    pub outcomes: Vec<String>,
+    pub bets: HashMap<String, Vec<Bet>>, // Per outcome
+    pub total_staked: Amount,
+    pub resolved: bool,
+    pub winning_outcome: Option<String>,
+    pub child_markets: Vec<String>,
+    pub expiry_time: Timestamp,
+    pub creator: Option<AccountOwner>, // Signer who created the market and resolves it
+    pub trading_fee_basis_points: u32, // Taken from every bet
+    pub fees_collected: Amount,        // Held in the application's account until royalties are paid
+    pub royalties_paid: Vec<RoyaltyPayment>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Bet {
+    pub bettor: ChainId,
+    pub amount: Amount,
+    pub timestamp: Timestamp,
+}
+
+/// A market set up at instantiation by the factory, which has already
+/// registered it.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MarketSetup {
+    pub market_id: String,
+    pub question: String,
+    pub outcomes: Vec<String>,
+    pub expiry_time: Timestamp,
+    pub creator: AccountOwner,
+    pub trading_fee_basis_points: u32,
+}
+
+/// A royalty paid out of a market's fees. Fees are transferred to the market's
+/// account as bets are placed, and royalties from it once the market resolves;
+/// stakes themselves are only recorded.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RoyaltyPayment {
+    pub market_id: String, // Market whose fees paid the royalty
+    pub role: RoyaltyRole,
+    pub recipient: Account,
+    pub amount: Amount,
+}
+
+/// Peer applications the market talks to.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Parameters {
+    /// Factory application keeping the market registry.
+    pub factory_id: ApplicationId,
+    /// Chain hosting the factory. Markets only run there, since they register,
+    /// resolve and pay royalties through synchronous calls to the factory;
+    /// instantiating one elsewhere fails and operations elsewhere are ignored.
+    pub factory_chain_id: ChainId,
+    /// Spawn handler application notified of resolutions, through its
+    /// instance on the factory chain.
+    pub spawn_handler_id: ApplicationId,
+}
+
+#[derive(Debug)]
+pub struct MarketAbi;
+
+impl ContractAbi for MarketAbi {
+    type Operation = Operation;
+    type Response = ResponseBytes;
+}
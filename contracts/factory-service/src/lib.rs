@@ -2,35 +2,15 @@ use linera_sdk::{
     service::{service, ServiceRuntime},
     views::{RegisterView, View},
     Service, WithServiceAbi,
-    base::{Amount, ChainId, Timestamp},
+    base::{AccountOwner, Timestamp},
 };
-use async_graphql::{EmptySubscription, Object, Request, Response, Schema};
-use std::collections::HashMap;
+use async_graphql::{EmptySubscription, Object, Request, Response, Schema, SimpleObject};
 use std::sync::Arc;
-use serde::{Deserialize, Serialize};
 
-// Share ABI with the contract
-pub use factory_contract::factory;
-
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
-pub struct MarketInfo {
-    pub market_id: String,
-    pub chain_id: ChainId,
-    pub question: String,
-    pub outcomes: Vec<String>,
-    pub parent_market_id: Option<String>,
-    pub child_markets: Vec<String>,
-    pub created_at: Timestamp,
-    pub creator: ChainId,
-    pub resolved: bool,
-}
-
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
-pub struct FactoryStateData {
-    pub markets: HashMap<String, MarketInfo>,
-    pub market_count: u64,
-    pub admin: Option<ChainId>,
-}
+// Share ABI and state types with the contract
+pub use fractal_abi::factory;
+use fractal_abi::market::RoyaltyPayment;
+use factory::FactoryStateData;
 
 #[derive(View, Default)]
 pub struct FactoryState {
@@ -52,6 +32,44 @@ impl FactoryService {
     }
 }
 
+/// A royalty share owed by a market.
+#[derive(SimpleObject)]
+pub struct RoyaltyView {
+    pub role: String,
+    pub recipient: Option<String>,
+    pub basis_points: u32,
+}
+
+impl From<&factory::RoyaltyShare> for RoyaltyView {
+    fn from(share: &factory::RoyaltyShare) -> Self {
+        RoyaltyView {
+            role: format!("{:?}", share.role),
+            recipient: share.recipient.map(|c| format!("{}", c)),
+            basis_points: share.basis_points,
+        }
+    }
+}
+
+/// A royalty a market paid out of its fees.
+#[derive(SimpleObject)]
+pub struct RoyaltyPaymentView {
+    pub market_id: String,
+    pub role: String,
+    pub recipient: String,
+    pub amount: String,
+}
+
+impl From<&RoyaltyPayment> for RoyaltyPaymentView {
+    fn from(payment: &RoyaltyPayment) -> Self {
+        RoyaltyPaymentView {
+            market_id: payment.market_id.clone(),
+            role: format!("{:?}", payment.role),
+            recipient: format!("{}", payment.recipient),
+            amount: format!("{}", payment.amount),
+        }
+    }
+}
+
 impl Service for FactoryService {
     type Parameters = factory::Parameters;
 
@@ -69,13 +87,66 @@ impl Service for FactoryService {
         // GraphQL schema
         struct QueryRoot {
             data: FactoryStateData,
+            parameters: factory::Parameters,
         }
 
         #[Object]
         impl QueryRoot {
             async fn market_count(&self) -> u64 { self.data.market_count }
             async fn market_ids(&self) -> Vec<String> { self.data.markets.keys().cloned().collect() }
-            async fn admin(&self) -> Option<String> { self.data.admin.map(|c| format!("{}", c)) }
+            async fn admins(&self) -> Vec<String> {
+                let admins = self.data.admin.current.iter().flat_map(|set| &set.owners);
+                admins.map(|c| format!("{}", c)).collect()
+            }
+            async fn admin_threshold(&self) -> Option<u32> { self.data.admin.current.as_ref().map(|set| set.threshold) }
+            /// Owners of a proposed admin handover, not yet accepted.
+            async fn proposed_admins(&self) -> Vec<String> {
+                let proposed = self.data.admin.pending.iter().flat_map(|t| &t.proposed.owners);
+                proposed.map(|c| format!("{}", c)).collect()
+            }
+            async fn admin_transfer_approvals(&self) -> Vec<String> {
+                let approvals = self.data.admin.pending.iter().flat_map(|t| &t.approvals);
+                approvals.map(|c| format!("{}", c)).collect()
+            }
+            async fn spawned_market(&self, spawn_id: String) -> Option<String> {
+                self.data.spawned_markets.get(&spawn_id).cloned()
+            }
+            async fn max_depth(&self) -> u32 { self.data.tree_limits.max_depth }
+            async fn max_children(&self) -> u32 { self.data.tree_limits.max_children }
+            async fn market_depth(&self, market_id: String) -> Option<u32> {
+                self.data.markets.get(&market_id).map(|m| m.depth)
+            }
+            async fn market_application_id(&self, market_id: String) -> Option<String> {
+                let market = self.data.markets.get(&market_id)?;
+                market.application_id.map(|id| format!("{}", id))
+            }
+            /// Microseconds since the epoch after which the market takes no bets.
+            async fn market_expiry_time(&self, market_id: String) -> Option<u64> {
+                self.data.markets.get(&market_id).map(|m| m.expiry_time.micros())
+            }
+            async fn market_royalties(&self, market_id: String) -> Vec<RoyaltyView> {
+                self.data
+                    .markets
+                    .get(&market_id)
+                    .map(|m| m.royalties.iter().map(RoyaltyView::from).collect())
+                    .unwrap_or_default()
+            }
+            /// Royalties paid to `recipient`, an account owner, on any chain.
+            async fn royalties_received(&self, recipient: String) -> async_graphql::Result<Vec<RoyaltyPaymentView>> {
+                let recipient: AccountOwner = recipient
+                    .parse()
+                    .map_err(|_| format!("invalid account owner: {recipient}"))?;
+                Ok(self
+                    .data
+                    .royalty_payments
+                    .iter()
+                    .filter(|payment| payment.recipient.owner == recipient)
+                    .map(RoyaltyPaymentView::from)
+                    .collect())
+            }
+            async fn market_module_id(&self) -> String { format!("{}", self.parameters.market_module_id) }
+            async fn spawn_handler_id(&self) -> String { format!("{}", self.parameters.spawn_handler_id) }
+            async fn spawn_handler_chain_id(&self) -> String { format!("{}", self.parameters.spawn_handler_chain_id) }
         }
 
         struct MutationRoot {
@@ -84,19 +155,32 @@ impl Service for FactoryService {
 
         #[Object]
         impl MutationRoot {
-            async fn create_market(&self, question: String, outcomes: Vec<String>, parent_market_id: Option<String>) -> bool {
+            /// Creates a market taking bets until `expiry_time`, in microseconds since the epoch,
+            /// with a fee of `trading_fee_basis_points` on every bet.
+            async fn create_market(
+                &self,
+                question: String,
+                outcomes: Vec<String>,
+                expiry_time: u64,
+                parent_market_id: Option<String>,
+                trading_fee_basis_points: Option<u32>,
+            ) -> bool {
                 self.runtime.schedule_operation(&factory::Operation::CreateMarket {
                     question,
                     outcomes,
-                    expiry_time: Timestamp::from_micros(0), // ignored by contract logic
+                    expiry_time: Timestamp::from_micros(expiry_time),
                     parent_market_id,
+                    trading_fee_basis_points: trading_fee_basis_points.unwrap_or(0),
                 });
                 true
             }
         }
 
         let schema = Schema::build(
-            QueryRoot { data: self.data() },
+            QueryRoot {
+                data: self.data(),
+                parameters: self.runtime.application_parameters(),
+            },
             MutationRoot { runtime: self.runtime.clone() },
             EmptySubscription,
         )
@@ -2,7 +2,7 @@
 
 use linera_sdk::{
     abi::WithContractAbi,
-    base::{Amount, ChainId, Timestamp},
+    base::{Account, AccountOwner, Amount, Timestamp},
     contract::ContractRuntime,
     views::{RegisterView, View},
     Contract,
@@ -11,64 +11,18 @@ use linera_sdk::{
 use serde::{Deserialize, Serialize};
 use linera_views::{batch::Batch, store::WritableKeyValueStore};
 use linera_views::context::Context;
-use std::collections::HashMap;
 use thiserror::Error;
 
-// ABI and parameters for the Factory contract (SDK 0.15)
-pub mod factory {
-    use super::*;
-    use linera_sdk::abi::ContractAbi;
-
-    #[derive(Debug, Deserialize, Serialize)]
-    pub enum Operation {
-        Initialize { admin: ChainId },
-        CreateMarket {
-            question: String,
-            outcomes: Vec<String>,
-            expiry_time: Timestamp,
-            parent_market_id: Option<String>,
-        },
-        RegisterMarket { market_info: MarketInfo },
-    }
-
-    #[derive(Debug, Clone, Deserialize, Serialize)]
-    pub struct ResponseBytes(pub Vec<u8>);
-
-    #[derive(Debug, Clone, Deserialize, Serialize)]
-    pub struct Parameters;
-
-    #[derive(Debug)]
-    pub struct FactoryAbi;
-
-    impl ContractAbi for FactoryAbi {
-        type Operation = Operation;
-        type Response = ResponseBytes;
-    }
-}
+pub use fractal_abi::{
+    admin, factory,
+    factory::{FactoryStateData, MarketInfo},
+};
+use fractal_abi::market::{self, MarketAbi, MarketSetup};
 
-/// Root state stored as a single register to avoid custom View macros
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
-pub struct FactoryStateData {
-    pub markets: HashMap<String, MarketInfo>,
-    pub market_count: u64,
-    pub admin: Option<ChainId>,
-}
+use admin::{AdminError, AdminRole};
 
 type FactoryState = RegisterView<FactoryStateData>;
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct MarketInfo {
-    pub market_id: String,
-    pub chain_id: ChainId,
-    pub question: String,
-    pub outcomes: Vec<String>,
-    pub parent_market_id: Option<String>,
-    pub child_markets: Vec<String>,
-    pub created_at: Timestamp,
-    pub creator: ChainId,
-    pub resolved: bool,
-}
-
 use factory::Operation;
 
 /// Messages for cross-chain communication
@@ -98,6 +52,8 @@ pub enum FactoryError {
     InvalidParameters,
     #[error("Market already exists")]
     MarketAlreadyExists,
+    #[error("Market tree limit exceeded")]
+    TreeLimitExceeded,
 }
 
 /// Factory contract implementation (SDK 0.15)
@@ -109,7 +65,7 @@ pub struct FactoryContract {
 impl Contract for FactoryContract {
     type Message = Message;
     type Parameters = factory::Parameters;
-    type InstantiationArgument = factory::Parameters;
+    type InstantiationArgument = factory::InstantiationArgument;
     type EventValue = ();
 
     async fn load(runtime: ContractRuntime<Self>) -> Self {
@@ -119,65 +75,212 @@ impl Contract for FactoryContract {
         FactoryContract { state, runtime }
     }
 
-    async fn instantiate(&mut self, _argument: Self::InstantiationArgument) {}
+    async fn instantiate(&mut self, argument: Self::InstantiationArgument) {
+        let mut data = self.state.get().clone();
+        data.admin.instantiated_by = self.runtime.authenticated_signer();
+        if let Some(admin) = argument.admin {
+            if let Err(error) = data.admin.initialize(admin) {
+                panic!("{error}");
+            }
+        }
+        self.state.set(data);
+    }
 
-    async fn execute_operation(&mut self, operation: Operation) -> factory::ResponseBytes {
+    async fn execute_operation(&mut self, operation: Operation) -> factory::Response {
         match operation {
             Operation::Initialize { admin } => {
-                if self.state.get().admin.is_some() {
-                    // already initialized; ignore
-                } else {
-                    let mut data = self.state.get().clone();
-                    data.admin = Some(admin);
-                    self.state.set(data);
-                }
+                self.update_admin(|role, signer| role.initialize_as(signer, admin))
             }
+            Operation::TransferAdmin { new_admin } => {
+                self.update_admin(|role, signer| role.propose(signer, new_admin))
+            }
+            Operation::AcceptAdmin => self.update_admin(|role, signer| role.accept(signer)),
+            Operation::CancelAdminTransfer => self.update_admin(|role, signer| role.cancel(signer)),
             Operation::CreateMarket {
                 question,
                 outcomes,
-                expiry_time: _expiry_time,
+                expiry_time,
                 parent_market_id,
+                trading_fee_basis_points,
             } => {
-                if outcomes.len() < 2 {
-                    // invalid; ignore
-                } else {
+                let Some(owner) = self.runtime.authenticated_signer() else {
+                    // markets are owned by their signer
+                    return factory::Response::Unauthorized;
+                };
+                self.create_market(NewMarket {
+                    question,
+                    outcomes,
+                    expiry_time,
+                    parent_market_id,
+                    creator: Account {
+                        chain_id: self.runtime.chain_id(),
+                        owner,
+                    },
+                    trading_fee_basis_points,
+                    seed_liquidity: Amount::ZERO,
+                    royalties: Vec::new(),
+                })
+            }
+            Operation::SpawnMarket {
+                spawn_id,
+                creator,
+                parent_market_id,
+                question,
+                outcomes,
+                expiry_time,
+                seed_liquidity,
+                trading_fee_basis_points,
+                royalties,
+            } => {
+                let spawn_handler_id = self.runtime.application_parameters().spawn_handler_id;
+                if self.runtime.authenticated_caller_id() != Some(spawn_handler_id) {
+                    // only the spawn handler may spawn markets
+                    return factory::Response::Unauthorized;
+                }
+                if let Some(market_id) = self.state.get().spawned_markets.get(&spawn_id) {
+                    // duplicate delivery; the market already exists
+                    return factory::Response::AlreadySpawned {
+                        spawn_id,
+                        market_id: market_id.clone(),
+                    };
+                }
+                let response = self.create_market(NewMarket {
+                    question,
+                    outcomes,
+                    expiry_time,
+                    parent_market_id,
+                    creator,
+                    trading_fee_basis_points,
+                    seed_liquidity,
+                    royalties,
+                });
+                if let factory::Response::MarketCreated { market_id } = &response {
                     let mut data = self.state.get().clone();
-                    let market_id = format!("market_{}", data.market_count);
-                    data.market_count = data.market_count.saturating_add(1);
-
+                    data.spawned_markets.insert(spawn_id, market_id.clone());
+                    self.state.set(data);
+                }
+                response
+            }
+            Operation::RegisterMarket { market_info } => {
+                let caller = self.runtime.authenticated_caller_id();
+                let signer = self.runtime.authenticated_signer();
+                let (Some(caller), Some(owner)) = (caller, signer) else {
+                    // only a market application, on behalf of its signer
+                    return factory::Response::Unauthorized;
+                };
+                let mut data = self.state.get().clone();
+                let market_id = market_info.market_id.clone();
+                if market_id.starts_with(factory::MARKET_ID_PREFIX)
+                    || market_info.parent_market_id.is_some()
+                {
+                    // reserved for created markets, and only they join trees; ignore
+                    factory::Response::InvalidParameters
+                } else if data.markets.contains_key(&market_id) {
+                    // already exists; ignore
+                    factory::Response::MarketAlreadyExists { market_id }
+                } else {
                     let market_info = MarketInfo {
-                        market_id: market_id.clone(),
                         chain_id: self.runtime.chain_id(),
-                        question: question.clone(),
-                        outcomes: outcomes.clone(),
-                        parent_market_id: parent_market_id.clone(),
+                        application_id: Some(caller),
                         child_markets: Vec::new(),
                         created_at: self.runtime.system_time(),
-                        creator: self.runtime.chain_id(),
+                        creator: Account {
+                            chain_id: self.runtime.chain_id(),
+                            owner,
+                        },
                         resolved: false,
+                        winning_outcome: None,
+                        seed_liquidity: Amount::ZERO,
+                        depth: 0,
+                        royalties: Vec::new(),
+                        ..market_info
                     };
-
-                    data.markets.insert(market_id.clone(), market_info);
-
-                    if let Some(parent_id) = parent_market_id {
-                        if let Some(parent_market) = data.markets.get_mut(&parent_id) {
-                            parent_market.child_markets.push(market_id);
-                        }
-                    }
+                    data.add_market(market_info);
                     self.state.set(data);
+                    factory::Response::MarketRegistered { market_id }
                 }
             }
-            Operation::RegisterMarket { market_info } => {
+            Operation::MarkMarketResolved {
+                market_id,
+                winning_outcome,
+            } => {
+                let caller = self.runtime.authenticated_caller_id();
                 let mut data = self.state.get().clone();
-                if data.markets.contains_key(&market_info.market_id) {
-                    // already exists; ignore
-                } else {
-                    data.markets.insert(market_info.market_id.clone(), market_info);
-                    self.state.set(data);
+                match data.markets.get_mut(&market_id) {
+                    Some(market) if caller.is_none() || market.application_id != caller => {
+                        // only the market's own application resolves it
+                        factory::Response::Unauthorized
+                    }
+                    Some(market) if market.outcomes.contains(&winning_outcome) => {
+                        market.resolved = true;
+                        market.winning_outcome = Some(winning_outcome);
+                        self.state.set(data);
+                        factory::Response::MarketResolved { market_id }
+                    }
+                    Some(_) => factory::Response::InvalidParameters,
+                    None => factory::Response::MarketNotFound { market_id },
+                }
+            }
+            Operation::MarketLineage { market_id } => {
+                let data = self.state.get();
+                let Some(market) = data.markets.get(&market_id) else {
+                    return factory::Response::MarketNotFound { market_id };
+                };
+                let mut ancestors = Vec::new();
+                let mut parent_id = market.parent_market_id.as_ref();
+                // Bounded by the number of markets in case the links ever form a cycle.
+                while let Some(id) = parent_id.filter(|_| ancestors.len() < data.markets.len()) {
+                    let Some(parent) = data.markets.get(id) else {
+                        break;
+                    };
+                    ancestors.push((id.clone(), parent.creator));
+                    parent_id = parent.parent_market_id.as_ref();
+                }
+                factory::Response::Lineage {
+                    creator: market.creator,
+                    ancestors,
+                }
+            }
+            Operation::MarketRoyalties { market_id } => match self.state.get().markets.get(&market_id) {
+                Some(market) => factory::Response::Royalties {
+                    royalties: market.royalties.clone(),
+                },
+                None => factory::Response::MarketNotFound { market_id },
+            },
+            Operation::MarketDetails { market_id } => match self.state.get().markets.get(&market_id) {
+                Some(market) => factory::Response::Market {
+                    market_info: Box::new(market.clone()),
+                },
+                None => factory::Response::MarketNotFound { market_id },
+            },
+            Operation::SetTreeLimits { limits } => {
+                let mut data = self.state.get().clone();
+                let signer = self.runtime.authenticated_signer();
+                if !signer.is_some_and(|signer| data.admin.is_admin(signer)) {
+                    // unauthorized; ignore
+                    return factory::Response::Unauthorized;
+                }
+                data.tree_limits = limits;
+                self.state.set(data);
+                factory::Response::Ok
+            }
+            Operation::RecordRoyalty { payment } => {
+                let caller = self.runtime.authenticated_caller_id();
+                let mut data = self.state.get().clone();
+                match data.markets.get(&payment.market_id) {
+                    Some(market) if caller.is_some() && market.application_id == caller => {
+                        data.royalty_payments.push(payment);
+                        self.state.set(data);
+                        factory::Response::Ok
+                    }
+                    // only the paying market's own application records its royalties
+                    Some(_) => factory::Response::Unauthorized,
+                    None => factory::Response::MarketNotFound {
+                        market_id: payment.market_id,
+                    },
                 }
             }
         }
-        factory::ResponseBytes(Vec::new())
     }
 
     async fn execute_message(&mut self, _message: Message) {}
@@ -195,6 +298,132 @@ impl Contract for FactoryContract {
     }
 }
 
+impl FactoryContract {
+    /// Applies `update` to the admin role on behalf of the signer, keeping the
+    /// role unchanged on failure. Unsigned operations are refused.
+    fn update_admin(
+        &mut self,
+        update: impl FnOnce(&mut AdminRole, AccountOwner) -> Result<(), AdminError>,
+    ) -> factory::Response {
+        let Some(signer) = self.runtime.authenticated_signer() else {
+            return factory::Response::Unauthorized;
+        };
+        let mut data = self.state.get().clone();
+        match update(&mut data.admin, signer) {
+            Ok(()) => {
+                self.state.set(data);
+                factory::Response::Ok
+            }
+            Err(AdminError::InvalidAdminSet) => factory::Response::InvalidParameters,
+            Err(_) => factory::Response::Unauthorized,
+        }
+    }
+
+    /// Adds a market to the registry, with a market application on this chain
+    /// to take its bets.
+    fn create_market(&mut self, market: NewMarket) -> factory::Response {
+        let NewMarket {
+            question,
+            outcomes,
+            expiry_time,
+            parent_market_id,
+            creator,
+            trading_fee_basis_points,
+            seed_liquidity,
+            mut royalties,
+        } = market;
+        if outcomes.len() < 2
+            || trading_fee_basis_points > 10_000
+            || !factory::royalties_fit(&royalties)
+        {
+            // invalid; ignore
+            return factory::Response::InvalidParameters;
+        }
+        let mut data = self.state.get().clone();
+        let depth = match &parent_market_id {
+            None => {
+                royalties.retain(|share| share.role != factory::RoyaltyRole::ParentCreator);
+                0
+            }
+            Some(parent_id) => {
+                let parent_market = match data.parent_for_child(parent_id) {
+                    Ok(parent_market) => parent_market,
+                    Err(response) => return response,
+                };
+                for share in &mut royalties {
+                    if share.role == factory::RoyaltyRole::ParentCreator {
+                        share.recipient = Some(parent_market.creator);
+                    }
+                }
+                parent_market.depth + 1
+            }
+        };
+        // Shares nobody could be found for are dropped.
+        royalties.retain(|share| share.recipient.is_some());
+        let market_id = format!("{}{}", factory::MARKET_ID_PREFIX, data.market_count);
+        if data.markets.contains_key(&market_id) {
+            // never overwrite a registry entry
+            return factory::Response::MarketAlreadyExists { market_id };
+        }
+        data.market_count = data.market_count.saturating_add(1);
+
+        let parameters = self.runtime.application_parameters();
+        let market_parameters = market::Parameters {
+            factory_id: self.runtime.application_id().forget_abi(),
+            factory_chain_id: self.runtime.chain_id(),
+            spawn_handler_id: parameters.spawn_handler_id,
+        };
+        let setup = MarketSetup {
+            market_id: market_id.clone(),
+            question: question.clone(),
+            outcomes: outcomes.clone(),
+            expiry_time,
+            creator: creator.owner,
+            trading_fee_basis_points,
+        };
+        let application_id = self.runtime.create_application::<MarketAbi, _, _>(
+            parameters.market_module_id.with_abi(),
+            &market_parameters,
+            &Some(setup),
+            Vec::new(),
+        );
+
+        let market_info = MarketInfo {
+            market_id: market_id.clone(),
+            chain_id: self.runtime.chain_id(),
+            application_id: Some(application_id.forget_abi()),
+            question,
+            outcomes,
+            parent_market_id,
+            child_markets: Vec::new(),
+            depth,
+            created_at: self.runtime.system_time(),
+            expiry_time,
+            creator,
+            resolved: false,
+            winning_outcome: None,
+            seed_liquidity,
+            royalties,
+        };
+
+        data.add_market(market_info);
+        self.state.set(data);
+        factory::Response::MarketCreated { market_id }
+    }
+}
+
+/// A market about to be added to the registry.
+struct NewMarket {
+    question: String,
+    outcomes: Vec<String>,
+    expiry_time: Timestamp,
+    parent_market_id: Option<String>,
+    creator: Account,
+    trading_fee_basis_points: u32,
+    seed_liquidity: Amount,
+    royalties: Vec<factory::RoyaltyShare>,
+}
+
 impl WithContractAbi for FactoryContract {
     type Abi = factory::FactoryAbi;
 }
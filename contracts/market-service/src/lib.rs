@@ -2,36 +2,14 @@ use linera_sdk::{
     service::{service, ServiceRuntime},
     views::{RegisterView, View},
     Service, WithServiceAbi,
-    base::{Amount, ChainId, Timestamp},
+    base::Amount,
 };
-use async_graphql::{EmptySubscription, Object, Request, Response, Schema};
-use std::collections::HashMap;
+use async_graphql::{EmptySubscription, Object, Request, Response, Schema, SimpleObject};
 use std::sync::Arc;
-use serde::{Deserialize, Serialize};
 
-// Share ABI with the contract
-pub use market_contract::market;
-
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
-pub struct Bet {
-    pub bettor: ChainId,
-    pub amount: Amount,
-    pub timestamp: Timestamp,
-}
-
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
-pub struct MarketStateData {
-    pub market_id: String,
-    pub question: String,
-    pub outcomes: Vec<String>,
-    pub bets: HashMap<String, Vec<Bet>>, // per-outcome bets
-    pub total_staked: Amount,
-    pub resolved: bool,
-    pub winning_outcome: Option<String>,
-    pub child_markets: Vec<String>,
-    pub expiry_time: Timestamp,
-    pub creator: Option<ChainId>,
-}
+// Share ABI and state types with the contract
+pub use fractal_abi::market;
+use market::{MarketStateData, RoyaltyPayment};
 
 #[derive(View, Default)]
 pub struct MarketState {
@@ -53,6 +31,25 @@ impl MarketService {
     }
 }
 
+#[derive(SimpleObject)]
+pub struct RoyaltyView {
+    pub market_id: String,
+    pub role: String,
+    pub recipient: String,
+    pub amount: String,
+}
+
+impl From<&RoyaltyPayment> for RoyaltyView {
+    fn from(payment: &RoyaltyPayment) -> Self {
+        RoyaltyView {
+            market_id: payment.market_id.clone(),
+            role: format!("{:?}", payment.role),
+            recipient: format!("{}", payment.recipient),
+            amount: format!("{}", payment.amount),
+        }
+    }
+}
+
 impl Service for MarketService {
     type Parameters = market::Parameters;
 
@@ -70,6 +67,7 @@ impl Service for MarketService {
         // GraphQL schema
         struct QueryRoot {
             data: MarketStateData,
+            parameters: market::Parameters,
         }
 
         #[Object]
@@ -82,6 +80,14 @@ impl Service for MarketService {
             async fn winning_outcome(&self) -> Option<String> { self.data.winning_outcome.clone() }
             async fn child_markets(&self) -> Vec<String> { self.data.child_markets.clone() }
             async fn expiry_time(&self) -> String { format!("{:?}", self.data.expiry_time) }
+            async fn trading_fee_basis_points(&self) -> u32 { self.data.trading_fee_basis_points }
+            async fn fees_collected(&self) -> String { format!("{}", self.data.fees_collected) }
+            async fn royalties_paid(&self) -> Vec<RoyaltyView> {
+                self.data.royalties_paid.iter().map(RoyaltyView::from).collect()
+            }
+            async fn factory_id(&self) -> String { format!("{}", self.parameters.factory_id) }
+            async fn factory_chain_id(&self) -> String { format!("{}", self.parameters.factory_chain_id) }
+            async fn spawn_handler_id(&self) -> String { format!("{}", self.parameters.spawn_handler_id) }
         }
 
         struct MutationRoot {
@@ -102,7 +108,10 @@ impl Service for MarketService {
         }
 
         let schema = Schema::build(
-            QueryRoot { data: self.data() },
+            QueryRoot {
+                data: self.data(),
+                parameters: self.runtime.application_parameters(),
+            },
             MutationRoot { runtime: self.runtime.clone() },
             EmptySubscription,
         )
@@ -1,8 +1,10 @@
 #![recursion_limit = "1024"]
 
+use fractal_abi::factory::{self, FactoryAbi, MarketInfo};
+use spawn_handler_abi::spawn_handler::{self, SpawnHandlerAbi};
 use linera_sdk::{
     abi::WithContractAbi,
-    base::{Amount, ChainId, Timestamp},
+    base::{Account, AccountOwner, Amount, ApplicationId},
     contract::ContractRuntime,
     views::{RegisterView, View},
     Contract,
@@ -14,66 +16,13 @@ use std::collections::HashMap;
 use thiserror::Error;
 
 // ABI and parameters for the Market contract (SDK 0.15)
-pub mod market {
-    use super::*;
-    use linera_sdk::abi::ContractAbi;
-
-    #[derive(Debug, Deserialize, Serialize)]
-    pub enum Operation {
-        CreateMarket {
-            market_id: String,
-            question: String,
-            outcomes: Vec<String>,
-            expiry_time: Timestamp,
-        },
-        PlaceBet {
-            outcome: String,
-            amount: Amount,
-        },
-        ResolveMarket {
-            winning_outcome: String,
-        },
-    }
-
-    #[derive(Debug, Clone, Deserialize, Serialize)]
-    pub struct ResponseBytes(pub Vec<u8>);
-
-    #[derive(Debug, Clone, Deserialize, Serialize)]
-    pub struct Parameters;
+pub use fractal_abi::market;
 
-    #[derive(Debug)]
-    pub struct MarketAbi;
-
-    impl ContractAbi for MarketAbi {
-        type Operation = Operation;
-        type Response = ResponseBytes;
-    }
-}
-
-/// Root state stored as a single register to avoid custom View macros
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
-pub struct MarketStateData {
-    pub market_id: String,
-    pub question: String,
-    pub outcomes: Vec<String>,
-    pub bets: HashMap<String, Vec<Bet>>,
-    pub total_staked: Amount,
-    pub resolved: bool,
-    pub winning_outcome: Option<String>,
-    pub child_markets: Vec<String>,
-    pub expiry_time: Timestamp,
-    pub creator: Option<ChainId>,
-}
+pub use market::{Bet, MarketStateData};
+use market::{MarketSetup, RoyaltyPayment};
 
 type MarketState = RegisterView<MarketStateData>;
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct Bet {
-    pub bettor: ChainId,
-    pub amount: Amount,
-    pub timestamp: Timestamp,
-}
-
 // Use ABI-defined operations
 use market::Operation;
 
@@ -117,7 +66,9 @@ pub struct MarketContract {
 impl Contract for MarketContract {
     type Message = Message;
     type Parameters = market::Parameters;
-    type InstantiationArgument = market::Parameters;
+    /// Set by the factory when it creates the market; `None` for markets
+    /// created later with `CreateMarket`.
+    type InstantiationArgument = Option<MarketSetup>;
     type EventValue = ();
 
     async fn load(runtime: ContractRuntime<Self>) -> Self {
@@ -127,11 +78,27 @@ impl Contract for MarketContract {
         MarketContract { state, runtime }
     }
 
-    async fn instantiate(&mut self, _argument: Self::InstantiationArgument) {
+    async fn instantiate(&mut self, argument: Self::InstantiationArgument) {
+        if self.runtime.application_parameters().factory_chain_id != self.runtime.chain_id() {
+            panic!("Markets must be created on the factory chain");
+        }
         let mut data = self.state.get().clone();
         data.total_staked = Amount::ZERO;
         data.resolved = false;
         data.creator = None;
+        if let Some(setup) = argument {
+            data.market_id = setup.market_id;
+            data.question = setup.question;
+            data.expiry_time = setup.expiry_time;
+            data.creator = Some(setup.creator);
+            data.trading_fee_basis_points = setup.trading_fee_basis_points.min(10_000);
+            data.bets = setup
+                .outcomes
+                .iter()
+                .map(|outcome| (outcome.clone(), Vec::new()))
+                .collect();
+            data.outcomes = setup.outcomes;
+        }
         self.state.set(data);
     }
 
@@ -142,64 +109,93 @@ impl Contract for MarketContract {
                 question,
                 outcomes,
                 expiry_time,
+                trading_fee_basis_points,
             } => {
-                if !self.state.get().market_id.is_empty() {
+                let creator = self.runtime.authenticated_signer();
+                let factory_chain_id = self.runtime.application_parameters().factory_chain_id;
+                if factory_chain_id != self.runtime.chain_id() {
+                    // markets only run on the factory chain; ignore
+                } else if !self.state.get().market_id.is_empty() {
                     // already exists; ignore
-                } else {
+                } else if trading_fee_basis_points > 10_000 {
+                    // invalid fee; ignore
+                } else if let Some(owner) = creator {
                     let mut data = self.state.get().clone();
                     data.market_id = market_id;
                     data.question = question;
                     data.outcomes = outcomes.clone();
                     data.expiry_time = expiry_time;
-                    data.creator = Some(self.runtime.chain_id());
+                    data.trading_fee_basis_points = trading_fee_basis_points;
+                    data.creator = Some(owner);
                     let mut bets: HashMap<String, Vec<Bet>> = HashMap::new();
                     for outcome in outcomes {
                         bets.insert(outcome, Vec::new());
                     }
                     data.bets = bets;
                     self.state.set(data);
+                    self.register_with_factory(owner);
+                } else {
+                    // markets are owned by their signer; ignore
                 }
             }
             Operation::PlaceBet { outcome, amount } => {
-                let data = self.state.get().clone();
+                let mut data = self.state.get().clone();
+                let fee = basis_points_of(amount, data.trading_fee_basis_points);
+                let signer = self.runtime.authenticated_signer();
                 if data.resolved {
                     // ignore if resolved
                 } else if self.runtime.system_time() > data.expiry_time {
                     // expired; ignore
-                } else if data.outcomes.contains(&outcome) {
-                    let mut data = self.state.get().clone();
+                } else if !data.outcomes.contains(&outcome) {
+                    // invalid outcome; ignore
+                } else if fee > Amount::ZERO && signer.is_none() {
+                    // the fee is paid from the signer's account; ignore
+                } else {
+                    if let Some(bettor) = signer.filter(|_| fee > Amount::ZERO) {
+                        let market_account = self.market_account();
+                        self.runtime.transfer(bettor, market_account, fee);
+                    }
+                    let stake = amount.saturating_sub(fee);
                     data.bets
                         .entry(outcome)
                         .or_default()
                         .push(Bet {
                             bettor: self.runtime.chain_id(),
-                            amount,
+                            amount: stake,
                             timestamp: self.runtime.system_time(),
                         });
-                    data.total_staked = data.total_staked.saturating_add(amount);
+                    data.total_staked = data.total_staked.saturating_add(stake);
+                    data.fees_collected = data.fees_collected.saturating_add(fee);
                     self.state.set(data);
                 }
             }
             Operation::ResolveMarket { winning_outcome } => {
                 let mut data = self.state.get().clone();
+                let signer = self.runtime.authenticated_signer();
                 if data.resolved {
                     // already resolved; ignore
                 } else if !data.outcomes.contains(&winning_outcome) {
                     // invalid outcome; ignore
-                } else if data.creator != Some(self.runtime.chain_id()) {
+                } else if data.creator.is_none() || data.creator != signer {
                     // unauthorized; ignore
                 } else {
                     data.resolved = true;
-                    data.winning_outcome = Some(winning_outcome);
+                    data.winning_outcome = Some(winning_outcome.clone());
                     self.state.set(data);
-                    // message dispatch omitted in v0.15 port minimal version
+                    self.notify_factory_resolved(winning_outcome.clone());
+                    self.pay_royalties();
+                    self.notify_spawn_handler(winning_outcome);
                 }
             }
         }
         market::ResponseBytes(Vec::new())
     }
 
-    async fn execute_message(&mut self, _message: Message) {}
+    async fn execute_message(&mut self, message: Message) {
+        match message {
+            Message::SpawnSubMarket { .. } => {}
+        }
+    }
 
     async fn store(self) {
         let mut batch = Batch::default();
@@ -214,6 +210,145 @@ impl Contract for MarketContract {
     }
 }
 
+impl MarketContract {
+    /// The factory, which runs on this chain and is called synchronously.
+    fn factory_id(&mut self) -> ApplicationId<FactoryAbi> {
+        self.runtime
+            .application_parameters()
+            .factory_id
+            .with_abi::<FactoryAbi>()
+    }
+
+    /// Registers the market, owned by `owner` on this chain, in the factory; the
+    /// whole operation fails if the registry refuses it, so market and registry
+    /// never disagree.
+    fn register_with_factory(&mut self, owner: AccountOwner) {
+        let factory_id = self.factory_id();
+        let data = self.state.get().clone();
+        let market_info = MarketInfo {
+            market_id: data.market_id,
+            chain_id: self.runtime.chain_id(),
+            application_id: Some(self.runtime.application_id().forget_abi()),
+            question: data.question,
+            outcomes: data.outcomes,
+            parent_market_id: None,
+            child_markets: data.child_markets,
+            depth: 0,
+            created_at: self.runtime.system_time(),
+            expiry_time: data.expiry_time,
+            creator: Account {
+                chain_id: self.runtime.chain_id(),
+                owner,
+            },
+            resolved: false,
+            winning_outcome: None,
+            seed_liquidity: Amount::ZERO,
+            royalties: Vec::new(),
+        };
+        let operation = factory::Operation::RegisterMarket { market_info };
+        match self.runtime.call_application(true, factory_id, &operation) {
+            factory::Response::MarketRegistered { .. } => {}
+            response => panic!("Factory refused market registration: {response:?}"),
+        }
+    }
+
+    /// Account holding the fees: the application's own on this chain.
+    fn market_account(&mut self) -> Account {
+        Account {
+            chain_id: self.runtime.chain_id(),
+            owner: AccountOwner::from(self.runtime.application_id().forget_abi()),
+        }
+    }
+
+    /// Pays every royalty share the factory recorded for this market out of the
+    /// fees collected, transferring the tokens to each recipient and recording
+    /// the payment in the factory.
+    fn pay_royalties(&mut self) {
+        let factory_id = self.factory_id();
+        let mut data = self.state.get().clone();
+        let operation = factory::Operation::MarketRoyalties {
+            market_id: data.market_id.clone(),
+        };
+        let factory::Response::Royalties { royalties } =
+            self.runtime.call_application(true, factory_id, &operation)
+        else {
+            return;
+        };
+        let fees = data.fees_collected;
+        let market_owner = self.market_account().owner;
+        for share in royalties {
+            let Some(recipient) = share.recipient else {
+                continue;
+            };
+            let amount = basis_points_of(fees, share.basis_points);
+            if amount == Amount::ZERO {
+                continue;
+            }
+            self.runtime.transfer(market_owner, recipient, amount);
+            data.fees_collected = data.fees_collected.saturating_sub(amount);
+            let payment = RoyaltyPayment {
+                market_id: data.market_id.clone(),
+                role: share.role,
+                recipient,
+                amount,
+            };
+            let operation = factory::Operation::RecordRoyalty {
+                payment: payment.clone(),
+            };
+            match self.runtime.call_application(true, factory_id, &operation) {
+                factory::Response::Ok => {}
+                response => panic!("Factory refused royalty record: {response:?}"),
+            }
+            data.royalties_paid.push(payment);
+        }
+        self.state.set(data);
+    }
+
+    /// Reports the resolution to the spawn handler, which checks the market
+    /// against the factory's registry before evaluating its rules.
+    fn notify_spawn_handler(&mut self, winning_outcome: String) {
+        let spawn_handler_id = self
+            .runtime
+            .application_parameters()
+            .spawn_handler_id
+            .with_abi::<SpawnHandlerAbi>();
+        let data = self.state.get();
+        let outcome_stakes = data
+            .outcomes
+            .iter()
+            .map(|outcome| {
+                let bets = data.bets.get(outcome).into_iter().flatten();
+                let stake = bets.fold(Amount::ZERO, |total, bet| total.saturating_add(bet.amount));
+                (outcome.clone(), stake)
+            })
+            .collect();
+        let operation = spawn_handler::Operation::NotifyResolved {
+            market_id: data.market_id.clone(),
+            winning_outcome,
+            outcome_stakes,
+        };
+        self.runtime.call_application(true, spawn_handler_id, &operation);
+    }
+
+    /// Marks the market resolved in the factory.
+    fn notify_factory_resolved(&mut self, winning_outcome: String) {
+        let factory_id = self.factory_id();
+        let operation = factory::Operation::MarkMarketResolved {
+            market_id: self.state.get().market_id.clone(),
+            winning_outcome,
+        };
+        match self.runtime.call_application(true, factory_id, &operation) {
+            factory::Response::MarketResolved { .. } => {}
+            response => panic!("Factory refused market resolution: {response:?}"),
+        }
+    }
+}
+
+/// `basis_points` ten-thousandths of `amount`, rounded down.
+fn basis_points_of(amount: Amount, basis_points: u32) -> Amount {
+    Amount::from_attos(u128::from(amount).saturating_mul(u128::from(basis_points)) / 10_000)
+}
+
 impl WithContractAbi for MarketContract {
     type Abi = market::MarketAbi;
 }
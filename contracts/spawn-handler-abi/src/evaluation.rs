@@ -0,0 +1,379 @@
+//! Evaluation of spawn rules against a resolution.
+//!
+//! Shared by the contract, which queues the resulting spawns, and the service,
+//! which reports them for hypothetical resolutions without touching state.
+
+use fractal_abi::admin::AdminRole;
+use linera_sdk::base::{AccountOwner, Amount, TimeDelta, Timestamp};
+use std::collections::HashMap;
+
+use crate::{
+    expr::{self, Expression},
+    pattern::PatternCache,
+    template::TemplateContext,
+    OutcomeSelector, Resolution, SpawnRule, SpawnTemplate, TriggerCondition,
+};
+
+/// A child market a rule would spawn, rendered but not yet queued.
+#[derive(Debug, Clone)]
+pub struct PlannedSpawn {
+    pub question: String,
+    pub outcomes: Vec<String>,
+    pub spawn_template: SpawnTemplate,
+    pub scheduled_time: Timestamp,
+    pub expiry_time: Timestamp,
+    pub seed_liquidity: Amount,
+}
+
+/// A rule whose trigger matched. `spawn` is `None` when the rule skips the
+/// winning outcome or its template no longer renders.
+#[derive(Debug, Clone)]
+pub struct RuleMatch {
+    pub rule: SpawnRule,
+    pub spawn: Option<PlannedSpawn>,
+}
+
+pub struct Evaluator<'a> {
+    pub patterns: &'a mut PatternCache,
+    /// Current time, seen by `CustomLogic` expressions as `now`.
+    pub now: Timestamp,
+    /// Admins' rules may watch any market, others' only markets their owner created.
+    pub admin: &'a AdminRole,
+}
+
+impl Evaluator<'_> {
+    /// Active rules in scope of `resolution` whose trigger matches it, highest
+    /// priority first with ties broken by id, up to and including the first
+    /// exclusive match. Scopes only count for rules that may watch their market.
+    pub fn matching_rules(
+        &mut self,
+        rules: &HashMap<String, SpawnRule>,
+        resolution: &Resolution,
+    ) -> Vec<RuleMatch> {
+        let mut ordered = rules
+            .values()
+            .filter(|rule| rule.active)
+            .filter(|rule| {
+                rule.scope.as_ref().is_none_or(|scope| {
+                    scope.contains(resolution)
+                        && self.may_watch(rule.created_by, &scope.market_id, resolution)
+                })
+            })
+            .collect::<Vec<_>>();
+        ordered.sort_by(|a, b| {
+            b.settings
+                .priority
+                .cmp(&a.settings.priority)
+                .then_with(|| a.rule_id.cmp(&b.rule_id))
+        });
+        let mut matches = Vec::new();
+        for rule in ordered {
+            let Some(delay_seconds) =
+                self.matched_delay(rule.created_by, &rule.trigger_condition, resolution)
+            else {
+                continue;
+            };
+            matches.push(RuleMatch {
+                rule: rule.clone(),
+                spawn: self.plan(rule, resolution, delay_seconds),
+            });
+            if rule.settings.exclusive {
+                break;
+            }
+        }
+        matches
+    }
+
+    /// Renders the child `rule` would spawn for `resolution`, due
+    /// `delay_seconds` after it.
+    pub fn plan(
+        &mut self,
+        rule: &SpawnRule,
+        resolution: &Resolution,
+        delay_seconds: u64,
+    ) -> Option<PlannedSpawn> {
+        let template = self.select_template(rule, &resolution.winning_outcome)?;
+        let scheduled_time = resolution
+            .resolved_at
+            .saturating_add(TimeDelta::from_secs(delay_seconds));
+        let expiry_time = expiry_time(&template, scheduled_time);
+        let context = TemplateContext {
+            resolution,
+            expiry_time,
+        };
+        let (question, outcomes) = template.render(&context)?;
+        Some(PlannedSpawn {
+            question,
+            outcomes,
+            seed_liquidity: template.seed_liquidity.amount(resolution.total_stake),
+            spawn_template: template,
+            scheduled_time,
+            expiry_time,
+        })
+    }
+
+    /// Picks the first outcome template matching `winning_outcome`, falling
+    /// back to the rule's default template unless the rule opts out.
+    pub fn select_template(&mut self, rule: &SpawnRule, winning_outcome: &str) -> Option<SpawnTemplate> {
+        let selected = rule.outcome_templates.iter().find(|outcome_template| {
+            match &outcome_template.selector {
+                OutcomeSelector::Exact { outcome } => outcome == winning_outcome,
+                OutcomeSelector::Pattern { pattern } => self.patterns.is_match(pattern, winning_outcome),
+            }
+        });
+        match selected {
+            Some(outcome_template) => Some(outcome_template.spawn_template.clone()),
+            None if rule.skip_unmatched_outcomes => None,
+            None => Some(rule.spawn_template.clone()),
+        }
+    }
+
+    /// Whether `owner` may target `market_id`, an ancestor of `resolution` or the
+    /// resolved market itself: admins may, and so may the market's creator.
+    pub fn may_watch(&self, owner: AccountOwner, market_id: &str, resolution: &Resolution) -> bool {
+        self.admin.is_admin(owner) || resolution.creator_of(market_id) == Some(owner)
+    }
+
+    /// Whether `condition`, in a rule owned by `owner`, matches `resolution`.
+    pub fn matches(
+        &mut self,
+        owner: AccountOwner,
+        condition: &TriggerCondition,
+        resolution: &Resolution,
+    ) -> bool {
+        self.matched_delay(owner, condition, resolution).is_some()
+    }
+
+    /// Delay before a spawn of `condition`, in a rule owned by `owner`, is due,
+    /// or `None` if it does not match `resolution`. Only `TimeDelay`s in
+    /// branches that matched count, the longest of them applying.
+    pub fn matched_delay(
+        &mut self,
+        owner: AccountOwner,
+        condition: &TriggerCondition,
+        resolution: &Resolution,
+    ) -> Option<u64> {
+        let question = &resolution.question;
+        let outcome = &resolution.winning_outcome;
+        let matched = match condition {
+            TriggerCondition::MarketResolution {
+                market_pattern,
+                outcome_pattern,
+            } => {
+                self.patterns.is_match(market_pattern, question)
+                    && self.patterns.is_match(outcome_pattern, outcome)
+            }
+            TriggerCondition::TimeDelay {
+                delay_seconds,
+                market_pattern,
+                outcome_pattern,
+            } => {
+                let matched = market_pattern
+                    .as_ref()
+                    .is_none_or(|pattern| self.patterns.is_match(pattern, question))
+                    && outcome_pattern
+                        .as_ref()
+                        .is_none_or(|pattern| self.patterns.is_match(pattern, outcome));
+                return matched.then_some(*delay_seconds);
+            }
+            TriggerCondition::CustomLogic { expression } => {
+                // Malformed or exhausted expressions never trigger
+                Expression::parse(expression)
+                    .ok()
+                    .and_then(|expression| {
+                        expression.evaluate(resolution, self.now, expr::DEFAULT_FUEL).ok()
+                    })
+                    .unwrap_or(false)
+            }
+            TriggerCondition::All { conditions } => {
+                return conditions.iter().try_fold(0, |delay: u64, condition| {
+                    Some(delay.max(self.matched_delay(owner, condition, resolution)?))
+                });
+            }
+            TriggerCondition::Any { conditions } => {
+                // `max` evaluates every branch, so each matching one contributes its delay
+                return conditions
+                    .iter()
+                    .filter_map(|condition| self.matched_delay(owner, condition, resolution))
+                    .max();
+            }
+            TriggerCondition::Not { condition } => {
+                self.matched_delay(owner, condition, resolution).is_none()
+            }
+            TriggerCondition::TotalStakeAbove { amount } => resolution.total_stake > *amount,
+            TriggerCondition::WinnerIn { outcomes } => outcomes.contains(outcome),
+            TriggerCondition::ParentInSubtree { root_market_id } => {
+                (resolution.market_id == *root_market_id
+                    || resolution.ancestors.contains(root_market_id))
+                    && self.may_watch(owner, root_market_id, resolution)
+            }
+            TriggerCondition::DepthBelow { max_depth } => resolution.depth < *max_depth,
+            // Fired by `ProcessPendingSpawns`, never by a resolution
+            TriggerCondition::Schedule { .. } => false,
+        };
+        matched.then_some(0)
+    }
+}
+
+pub fn expiry_time(template: &SpawnTemplate, scheduled_time: Timestamp) -> Timestamp {
+    scheduled_time.saturating_add(TimeDelta::from_secs(template.expiry_offset_seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{OutcomeTemplate, RuleSettings, SeedLiquidity};
+
+    fn owner(byte: u8) -> AccountOwner {
+        AccountOwner::Address20([byte; 20])
+    }
+
+    fn template(question: &str) -> SpawnTemplate {
+        SpawnTemplate {
+            question_template: question.to_string(),
+            outcomes: vec!["Yes".to_string(), "No".to_string()],
+            expiry_offset_seconds: 3_600,
+            seed_liquidity: SeedLiquidity::Absolute { amount: Amount::ZERO },
+        }
+    }
+
+    fn rule(rule_id: &str, priority: i32, exclusive: bool) -> SpawnRule {
+        SpawnRule {
+            rule_id: rule_id.to_string(),
+            trigger_condition: TriggerCondition::WinnerIn {
+                outcomes: vec!["Yes".to_string()],
+            },
+            spawn_template: template("What after {outcome}?"),
+            outcome_templates: Vec::new(),
+            skip_unmatched_outcomes: false,
+            settings: RuleSettings {
+                priority,
+                exclusive,
+                ..RuleSettings::default()
+            },
+            scope: None,
+            active: true,
+            created_by: owner(1),
+            version: 1,
+        }
+    }
+
+    fn resolution(winning_outcome: &str) -> Resolution {
+        Resolution {
+            market_id: "m1".to_string(),
+            question: "Who wins?".to_string(),
+            winning_outcome: winning_outcome.to_string(),
+            total_stake: Amount::from_tokens(100),
+            outcome_stakes: Vec::new(),
+            depth: 0,
+            ancestors: Vec::new(),
+            resolved_at: Timestamp::from(0),
+            creators: vec![("m1".to_string(), owner(1))],
+        }
+    }
+
+    fn matching(rules: Vec<SpawnRule>, winning_outcome: &str) -> Vec<RuleMatch> {
+        let rules = rules
+            .into_iter()
+            .map(|rule| (rule.rule_id.clone(), rule))
+            .collect::<HashMap<_, _>>();
+        let mut patterns = PatternCache::default();
+        let admin = AdminRole::default();
+        let mut evaluator = Evaluator {
+            patterns: &mut patterns,
+            now: Timestamp::from(0),
+            admin: &admin,
+        };
+        evaluator.matching_rules(&rules, &resolution(winning_outcome))
+    }
+
+    fn rule_ids(matches: &[RuleMatch]) -> Vec<&str> {
+        matches.iter().map(|m| m.rule.rule_id.as_str()).collect()
+    }
+
+    #[test]
+    fn orders_by_priority_then_id() {
+        let mut inactive = rule("e", 10, false);
+        inactive.active = false;
+        let rules = vec![
+            rule("c", 1, false),
+            rule("b", 5, false),
+            inactive,
+            rule("d", -2, false),
+            rule("a", 5, false),
+        ];
+        assert_eq!(rule_ids(&matching(rules, "Yes")), ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn stops_after_the_first_exclusive_match() {
+        let rules = vec![rule("a", 5, false), rule("b", 3, true), rule("c", 1, false)];
+        assert_eq!(rule_ids(&matching(rules, "Yes")), ["a", "b"]);
+
+        // An exclusive rule that does not match lets later rules through.
+        let mut unmatched = rule("b", 3, true);
+        unmatched.trigger_condition = TriggerCondition::WinnerIn {
+            outcomes: vec!["No".to_string()],
+        };
+        let rules = vec![rule("a", 5, false), unmatched, rule("c", 1, false)];
+        assert_eq!(rule_ids(&matching(rules, "Yes")), ["a", "c"]);
+    }
+
+    fn per_outcome_rule() -> SpawnRule {
+        let mut rule = rule("r", 0, false);
+        rule.trigger_condition = TriggerCondition::DepthBelow { max_depth: 10 };
+        rule.outcome_templates = vec![
+            OutcomeTemplate {
+                selector: OutcomeSelector::Exact {
+                    outcome: "Yes".to_string(),
+                },
+                spawn_template: template("Will {outcome} last?"),
+            },
+            OutcomeTemplate {
+                selector: OutcomeSelector::Pattern {
+                    pattern: "^n".to_string(),
+                },
+                spawn_template: template("Why {outcome}?"),
+            },
+            OutcomeTemplate {
+                selector: OutcomeSelector::Pattern {
+                    pattern: ".*".to_string(),
+                },
+                spawn_template: template("Not reached"),
+            },
+        ];
+        rule
+    }
+
+    fn question(matches: &[RuleMatch]) -> Option<&str> {
+        matches[0].spawn.as_ref().map(|spawn| spawn.question.as_str())
+    }
+
+    #[test]
+    fn uses_the_first_matching_outcome_template() {
+        let matches = matching(vec![per_outcome_rule()], "Yes");
+        assert_eq!(question(&matches), Some("Will Yes last?"));
+        // Patterns are case-insensitive, and checked in order.
+        let matches = matching(vec![per_outcome_rule()], "No");
+        assert_eq!(question(&matches), Some("Why No?"));
+        let matches = matching(vec![rule("r", 0, false)], "Yes");
+        assert_eq!(question(&matches), Some("What after Yes?"));
+    }
+
+    #[test]
+    fn falls_back_to_the_default_template_unless_skipping() {
+        let mut rule = per_outcome_rule();
+        rule.outcome_templates.pop();
+        let matches = matching(vec![rule.clone()], "Maybe");
+        assert_eq!(question(&matches), Some("What after Maybe?"));
+
+        // A skipping rule still matches, so an exclusive one still stops
+        // evaluation, but plans no spawn.
+        rule.skip_unmatched_outcomes = true;
+        let matches = matching(vec![rule.clone()], "Maybe");
+        assert_eq!(rule_ids(&matches), ["r"]);
+        assert_eq!(question(&matches), None);
+        let matches = matching(vec![rule], "Yes");
+        assert_eq!(question(&matches), Some("Will Yes last?"));
+    }
+}
@@ -0,0 +1,786 @@
+//! Expression language for `TriggerCondition::CustomLogic`.
+//!
+//! Expressions are parsed and type-checked when a rule is created and
+//! evaluated against a [`Resolution`] with a fuel budget, so evaluation is
+//! deterministic and bounded.
+//!
+//! ```text
+//! or      := and ("||" and)*
+//! and     := unary ("&&" unary)*
+//! unary   := "!" unary | compare
+//! compare := sum (("==" | "!=" | "<" | "<=" | ">" | ">=") sum)*
+//! sum     := product (("+" | "-") product)*
+//! product := atom (("*" | "/" | "%") atom)*
+//! atom    := integer | string | "true" | "false" | variable
+//!          | function "(" (or ("," or)*)? ")" | "(" or ")"
+//! ```
+//!
+//! Variables: `market_id`, `question`, `winning_outcome` (strings), `total_stake`
+//! (whole tokens), `depth`, `resolved_at` and `now` (seconds since the epoch).
+//!
+//! Functions: `stake(outcome)` (whole tokens), `share_bps(outcome)` (pool share
+//! in basis points), `contains(text, part)`, `starts_with(text, part)`,
+//! `ends_with(text, part)` (all case-insensitive), `lower(text)` and `len(text)`.
+
+use linera_sdk::base::{Amount, Timestamp};
+use std::fmt;
+
+use crate::Resolution;
+
+/// Longest expression accepted in a spawn rule.
+pub const MAX_SOURCE_LENGTH: usize = 1024;
+
+/// Fuel available to a single evaluation.
+pub const DEFAULT_FUEL: u64 = 10_000;
+
+/// Deepest nesting of parentheses, negations and calls.
+const MAX_NESTING: usize = 32;
+
+/// Why an expression was rejected, with the 1-based column of the problem.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "column {}: {}", self.column, self.message)
+    }
+}
+
+/// Why an evaluation stopped before producing a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvalError {
+    OutOfFuel,
+    Overflow,
+    DivisionByZero,
+    TypeMismatch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    Int,
+    Str,
+    Bool,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Type::Int => "integer",
+            Type::Str => "string",
+            Type::Bool => "boolean",
+        };
+        formatter.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Int(i128),
+    Str(String),
+    Bool(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Or,
+    And,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinaryOp {
+    /// Result type of the operator applied to the given operand types.
+    fn result_type(self, left: Type, right: Type) -> Option<Type> {
+        match self {
+            BinaryOp::Or | BinaryOp::And => {
+                (left == Type::Bool && right == Type::Bool).then_some(Type::Bool)
+            }
+            BinaryOp::Eq | BinaryOp::Ne => (left == right).then_some(Type::Bool),
+            BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+                (left == Type::Int && right == Type::Int).then_some(Type::Bool)
+            }
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => {
+                (left == Type::Int && right == Type::Int).then_some(Type::Int)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Variable {
+    MarketId,
+    Question,
+    WinningOutcome,
+    TotalStake,
+    Depth,
+    ResolvedAt,
+    Now,
+}
+
+impl Variable {
+    fn lookup(name: &str) -> Option<(Self, Type)> {
+        let variable = match name {
+            "market_id" => (Variable::MarketId, Type::Str),
+            "question" => (Variable::Question, Type::Str),
+            "winning_outcome" => (Variable::WinningOutcome, Type::Str),
+            "total_stake" => (Variable::TotalStake, Type::Int),
+            "depth" => (Variable::Depth, Type::Int),
+            "resolved_at" => (Variable::ResolvedAt, Type::Int),
+            "now" => (Variable::Now, Type::Int),
+            _ => return None,
+        };
+        Some(variable)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Function {
+    Stake,
+    ShareBps,
+    Contains,
+    StartsWith,
+    EndsWith,
+    Lower,
+    Len,
+}
+
+impl Function {
+    fn lookup(name: &str) -> Option<(Self, &'static [Type], Type)> {
+        let function: (Self, &'static [Type], Type) = match name {
+            "stake" => (Function::Stake, &[Type::Str], Type::Int),
+            "share_bps" => (Function::ShareBps, &[Type::Str], Type::Int),
+            "contains" => (Function::Contains, &[Type::Str, Type::Str], Type::Bool),
+            "starts_with" => (Function::StartsWith, &[Type::Str, Type::Str], Type::Bool),
+            "ends_with" => (Function::EndsWith, &[Type::Str, Type::Str], Type::Bool),
+            "lower" => (Function::Lower, &[Type::Str], Type::Str),
+            "len" => (Function::Len, &[Type::Str], Type::Int),
+            _ => return None,
+        };
+        Some(function)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Int(i128),
+    Str(String),
+    Bool(bool),
+    Var(Variable),
+    Not(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Int(i128),
+    Str(String),
+    Ident(String),
+    LParen,
+    RParen,
+    Comma,
+    Not,
+    AndAnd,
+    OrOr,
+    EqEq,
+    NotEq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+}
+
+#[derive(Debug, Clone)]
+struct Spanned {
+    token: Token,
+    column: usize,
+}
+
+fn error(column: usize, message: impl Into<String>) -> ParseError {
+    ParseError {
+        column,
+        message: message.into(),
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Spanned>, ParseError> {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+    while index < chars.len() {
+        let column = index + 1;
+        let current = chars[index];
+        let next = chars.get(index + 1).copied();
+        let (token, length) = match current {
+            c if c.is_whitespace() => {
+                index += 1;
+                continue;
+            }
+            c if c.is_ascii_digit() => {
+                let end = chars[index..]
+                    .iter()
+                    .position(|c| !c.is_ascii_digit() && *c != '_')
+                    .map_or(chars.len(), |offset| index + offset);
+                let digits = chars[index..end]
+                    .iter()
+                    .filter(|c| **c != '_')
+                    .collect::<String>();
+                let value = digits
+                    .parse::<i128>()
+                    .map_err(|_| error(column, "integer literal is too large"))?;
+                (Token::Int(value), end - index)
+            }
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let end = chars[index..]
+                    .iter()
+                    .position(|c| !c.is_ascii_alphanumeric() && *c != '_')
+                    .map_or(chars.len(), |offset| index + offset);
+                let name = chars[index..end].iter().collect::<String>();
+                (Token::Ident(name), end - index)
+            }
+            '"' => {
+                let mut text = String::new();
+                let mut end = index + 1;
+                loop {
+                    match chars.get(end) {
+                        None => return Err(error(column, "unterminated string literal")),
+                        Some('"') => break,
+                        Some('\\') => match chars.get(end + 1) {
+                            Some(&escaped @ ('"' | '\\')) => {
+                                text.push(escaped);
+                                end += 2;
+                            }
+                            _ => return Err(error(end + 1, "unsupported escape sequence")),
+                        },
+                        Some(&c) => {
+                            text.push(c);
+                            end += 1;
+                        }
+                    }
+                }
+                (Token::Str(text), end + 1 - index)
+            }
+            '(' => (Token::LParen, 1),
+            ')' => (Token::RParen, 1),
+            ',' => (Token::Comma, 1),
+            '+' => (Token::Plus, 1),
+            '-' => (Token::Minus, 1),
+            '*' => (Token::Star, 1),
+            '/' => (Token::Slash, 1),
+            '%' => (Token::Percent, 1),
+            '&' if next == Some('&') => (Token::AndAnd, 2),
+            '|' if next == Some('|') => (Token::OrOr, 2),
+            '=' if next == Some('=') => (Token::EqEq, 2),
+            '!' if next == Some('=') => (Token::NotEq, 2),
+            '!' => (Token::Not, 1),
+            '<' if next == Some('=') => (Token::Le, 2),
+            '<' => (Token::Lt, 1),
+            '>' if next == Some('=') => (Token::Ge, 2),
+            '>' => (Token::Gt, 1),
+            '=' => return Err(error(column, "expected `==`, found `=`")),
+            '&' => return Err(error(column, "expected `&&`, found `&`")),
+            '|' => return Err(error(column, "expected `||`, found `|`")),
+            c => return Err(error(column, format!("unexpected character `{c}`"))),
+        };
+        tokens.push(Spanned { token, column });
+        index += length;
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Spanned>,
+    position: usize,
+    nesting: usize,
+    end_column: usize,
+}
+
+type Parsed = Result<(Node, Type), ParseError>;
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|spanned| &spanned.token)
+    }
+
+    fn column(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.end_column, |spanned| spanned.column)
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position)?.token.clone();
+        self.position += 1;
+        Some(token)
+    }
+
+    fn expect(&mut self, expected: Token, description: &str) -> Result<(), ParseError> {
+        if self.peek() == Some(&expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(error(self.column(), format!("expected {description}")))
+        }
+    }
+
+    fn enter(&mut self) -> Result<(), ParseError> {
+        self.nesting += 1;
+        if self.nesting > MAX_NESTING {
+            return Err(error(
+                self.column(),
+                format!("expression is nested more than {MAX_NESTING} levels deep"),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Parses a left-associative chain of the given operators.
+    fn binary(
+        &mut self,
+        operators: &[(Token, BinaryOp)],
+        operand: fn(&mut Self) -> Parsed,
+    ) -> Parsed {
+        let (mut node, mut node_type) = operand(self)?;
+        while let Some(op) = self.peek().and_then(|token| {
+            operators
+                .iter()
+                .find(|(candidate, _)| candidate == token)
+                .map(|(_, op)| *op)
+        }) {
+            let column = self.column();
+            self.position += 1;
+            let (right, right_type) = operand(self)?;
+            node_type = op.result_type(node_type, right_type).ok_or_else(|| {
+                error(
+                    column,
+                    format!("operator cannot be applied to {node_type} and {right_type}"),
+                )
+            })?;
+            node = Node::Binary(op, Box::new(node), Box::new(right));
+        }
+        Ok((node, node_type))
+    }
+
+    fn or(&mut self) -> Parsed {
+        self.binary(&[(Token::OrOr, BinaryOp::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Parsed {
+        self.binary(&[(Token::AndAnd, BinaryOp::And)], Self::unary)
+    }
+
+    fn unary(&mut self) -> Parsed {
+        if self.peek() != Some(&Token::Not) {
+            return self.compare();
+        }
+        let column = self.column();
+        self.position += 1;
+        self.enter()?;
+        let (operand, operand_type) = self.unary()?;
+        self.nesting -= 1;
+        if operand_type != Type::Bool {
+            return Err(error(column, format!("`!` expects a boolean, found {operand_type}")));
+        }
+        Ok((Node::Not(Box::new(operand)), Type::Bool))
+    }
+
+    fn compare(&mut self) -> Parsed {
+        self.binary(
+            &[
+                (Token::EqEq, BinaryOp::Eq),
+                (Token::NotEq, BinaryOp::Ne),
+                (Token::Lt, BinaryOp::Lt),
+                (Token::Le, BinaryOp::Le),
+                (Token::Gt, BinaryOp::Gt),
+                (Token::Ge, BinaryOp::Ge),
+            ],
+            Self::sum,
+        )
+    }
+
+    fn sum(&mut self) -> Parsed {
+        self.binary(
+            &[(Token::Plus, BinaryOp::Add), (Token::Minus, BinaryOp::Sub)],
+            Self::product,
+        )
+    }
+
+    fn product(&mut self) -> Parsed {
+        self.binary(
+            &[
+                (Token::Star, BinaryOp::Mul),
+                (Token::Slash, BinaryOp::Div),
+                (Token::Percent, BinaryOp::Rem),
+            ],
+            Self::atom,
+        )
+    }
+
+    fn atom(&mut self) -> Parsed {
+        let column = self.column();
+        match self.advance() {
+            Some(Token::Int(value)) => Ok((Node::Int(value), Type::Int)),
+            Some(Token::Str(text)) => Ok((Node::Str(text), Type::Str)),
+            Some(Token::LParen) => {
+                self.enter()?;
+                let inner = self.or()?;
+                self.expect(Token::RParen, "`)`")?;
+                self.nesting -= 1;
+                Ok(inner)
+            }
+            Some(Token::Ident(name)) if self.peek() == Some(&Token::LParen) => {
+                self.call(&name, column)
+            }
+            Some(Token::Ident(name)) => match name.as_str() {
+                "true" => Ok((Node::Bool(true), Type::Bool)),
+                "false" => Ok((Node::Bool(false), Type::Bool)),
+                _ => {
+                    let (variable, variable_type) = Variable::lookup(&name)
+                        .ok_or_else(|| error(column, format!("unknown variable `{name}`")))?;
+                    Ok((Node::Var(variable), variable_type))
+                }
+            },
+            Some(_) => Err(error(column, "expected a value")),
+            None => Err(error(column, "unexpected end of expression")),
+        }
+    }
+
+    fn call(&mut self, name: &str, column: usize) -> Parsed {
+        let (function, parameters, result) = Function::lookup(name)
+            .ok_or_else(|| error(column, format!("unknown function `{name}`")))?;
+        self.position += 1;
+        self.enter()?;
+        let mut arguments = Vec::new();
+        if self.peek() != Some(&Token::RParen) {
+            loop {
+                let argument_column = self.column();
+                let (argument, argument_type) = self.or()?;
+                if let Some(expected) = parameters.get(arguments.len()) {
+                    if *expected != argument_type {
+                        return Err(error(
+                            argument_column,
+                            format!("`{name}` expects a {expected}, found {argument_type}"),
+                        ));
+                    }
+                }
+                arguments.push(argument);
+                if self.peek() != Some(&Token::Comma) {
+                    break;
+                }
+                self.position += 1;
+            }
+        }
+        self.expect(Token::RParen, "`)` after arguments")?;
+        self.nesting -= 1;
+        if arguments.len() != parameters.len() {
+            return Err(error(
+                column,
+                format!(
+                    "`{name}` takes {} argument(s), found {}",
+                    parameters.len(),
+                    arguments.len()
+                ),
+            ));
+        }
+        Ok((Node::Call(function, arguments), result))
+    }
+}
+
+/// A parsed, type-checked boolean expression.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        if source.len() > MAX_SOURCE_LENGTH {
+            return Err(error(
+                MAX_SOURCE_LENGTH,
+                format!("expression is longer than {MAX_SOURCE_LENGTH} bytes"),
+            ));
+        }
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            nesting: 0,
+            end_column: source.chars().count() + 1,
+        };
+        let (root, root_type) = parser.or()?;
+        if parser.peek().is_some() {
+            return Err(error(parser.column(), "expected an operator or end of expression"));
+        }
+        if root_type != Type::Bool {
+            return Err(error(1, format!("expression must be a boolean, found {root_type}")));
+        }
+        Ok(Expression { root })
+    }
+
+    /// Evaluates the expression, spending one unit of fuel per step plus the
+    /// length of any string scanned.
+    pub fn evaluate(
+        &self,
+        resolution: &Resolution,
+        now: Timestamp,
+        fuel: u64,
+    ) -> Result<bool, EvalError> {
+        let mut evaluator = Evaluator {
+            resolution,
+            now,
+            fuel,
+        };
+        match evaluator.eval(&self.root)? {
+            Value::Bool(result) => Ok(result),
+            _ => Err(EvalError::TypeMismatch),
+        }
+    }
+}
+
+struct Evaluator<'a> {
+    resolution: &'a Resolution,
+    now: Timestamp,
+    fuel: u64,
+}
+
+fn whole_tokens(amount: Amount) -> i128 {
+    i128::try_from(crate::whole_tokens(amount)).unwrap_or(i128::MAX)
+}
+
+fn seconds(timestamp: Timestamp) -> i128 {
+    i128::from(timestamp.micros() / 1_000_000)
+}
+
+impl Evaluator<'_> {
+    fn burn(&mut self, amount: u64) -> Result<(), EvalError> {
+        self.fuel = self.fuel.checked_sub(amount).ok_or(EvalError::OutOfFuel)?;
+        Ok(())
+    }
+
+    fn eval(&mut self, node: &Node) -> Result<Value, EvalError> {
+        self.burn(1)?;
+        let value = match node {
+            Node::Int(value) => Value::Int(*value),
+            Node::Str(text) => Value::Str(text.clone()),
+            Node::Bool(value) => Value::Bool(*value),
+            Node::Var(variable) => self.variable(*variable),
+            Node::Not(operand) => Value::Bool(!self.eval_bool(operand)?),
+            Node::Binary(BinaryOp::And, left, right) => {
+                Value::Bool(self.eval_bool(left)? && self.eval_bool(right)?)
+            }
+            Node::Binary(BinaryOp::Or, left, right) => {
+                Value::Bool(self.eval_bool(left)? || self.eval_bool(right)?)
+            }
+            Node::Binary(BinaryOp::Eq, left, right) => {
+                Value::Bool(self.eval(left)? == self.eval(right)?)
+            }
+            Node::Binary(BinaryOp::Ne, left, right) => {
+                Value::Bool(self.eval(left)? != self.eval(right)?)
+            }
+            Node::Binary(op, left, right) => {
+                let left = self.eval_int(left)?;
+                let right = self.eval_int(right)?;
+                match op {
+                    BinaryOp::Lt => Value::Bool(left < right),
+                    BinaryOp::Le => Value::Bool(left <= right),
+                    BinaryOp::Gt => Value::Bool(left > right),
+                    BinaryOp::Ge => Value::Bool(left >= right),
+                    BinaryOp::Add => Value::Int(left.checked_add(right).ok_or(EvalError::Overflow)?),
+                    BinaryOp::Sub => Value::Int(left.checked_sub(right).ok_or(EvalError::Overflow)?),
+                    BinaryOp::Mul => Value::Int(left.checked_mul(right).ok_or(EvalError::Overflow)?),
+                    BinaryOp::Div | BinaryOp::Rem if right == 0 => {
+                        return Err(EvalError::DivisionByZero)
+                    }
+                    BinaryOp::Div => Value::Int(left.checked_div(right).ok_or(EvalError::Overflow)?),
+                    BinaryOp::Rem => Value::Int(left.checked_rem(right).ok_or(EvalError::Overflow)?),
+                    BinaryOp::Or | BinaryOp::And | BinaryOp::Eq | BinaryOp::Ne => {
+                        return Err(EvalError::TypeMismatch)
+                    }
+                }
+            }
+            Node::Call(function, arguments) => self.call(*function, arguments)?,
+        };
+        Ok(value)
+    }
+
+    fn eval_bool(&mut self, node: &Node) -> Result<bool, EvalError> {
+        match self.eval(node)? {
+            Value::Bool(value) => Ok(value),
+            _ => Err(EvalError::TypeMismatch),
+        }
+    }
+
+    fn eval_int(&mut self, node: &Node) -> Result<i128, EvalError> {
+        match self.eval(node)? {
+            Value::Int(value) => Ok(value),
+            _ => Err(EvalError::TypeMismatch),
+        }
+    }
+
+    fn eval_str(&mut self, node: &Node) -> Result<String, EvalError> {
+        match self.eval(node)? {
+            Value::Str(text) => {
+                self.burn(text.len() as u64)?;
+                Ok(text)
+            }
+            _ => Err(EvalError::TypeMismatch),
+        }
+    }
+
+    fn variable(&self, variable: Variable) -> Value {
+        let resolution = self.resolution;
+        match variable {
+            Variable::MarketId => Value::Str(resolution.market_id.clone()),
+            Variable::Question => Value::Str(resolution.question.clone()),
+            Variable::WinningOutcome => Value::Str(resolution.winning_outcome.clone()),
+            Variable::TotalStake => Value::Int(whole_tokens(resolution.total_stake)),
+            Variable::Depth => Value::Int(i128::from(resolution.depth)),
+            Variable::ResolvedAt => Value::Int(seconds(resolution.resolved_at)),
+            Variable::Now => Value::Int(seconds(self.now)),
+        }
+    }
+
+    fn call(&mut self, function: Function, arguments: &[Node]) -> Result<Value, EvalError> {
+        let mut strings = Vec::with_capacity(arguments.len());
+        for argument in arguments {
+            strings.push(self.eval_str(argument)?);
+        }
+        let value = match (function, strings.as_slice()) {
+            (Function::Stake, [outcome]) => {
+                Value::Int(whole_tokens(self.resolution.outcome_stake(outcome)))
+            }
+            (Function::ShareBps, [outcome]) => {
+                let total = u128::from(self.resolution.total_stake);
+                let stake = u128::from(self.resolution.outcome_stake(outcome));
+                let share = match total {
+                    0 => 0,
+                    _ => stake.saturating_mul(10_000) / total,
+                };
+                Value::Int(i128::try_from(share).unwrap_or(i128::MAX))
+            }
+            (Function::Contains, [text, part]) => {
+                Value::Bool(text.to_lowercase().contains(&part.to_lowercase()))
+            }
+            (Function::StartsWith, [text, part]) => {
+                Value::Bool(text.to_lowercase().starts_with(&part.to_lowercase()))
+            }
+            (Function::EndsWith, [text, part]) => {
+                Value::Bool(text.to_lowercase().ends_with(&part.to_lowercase()))
+            }
+            (Function::Lower, [text]) => Value::Str(text.to_lowercase()),
+            (Function::Len, [text]) => Value::Int(text.chars().count() as i128),
+            _ => return Err(EvalError::TypeMismatch),
+        };
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolution() -> Resolution {
+        Resolution {
+            market_id: "m3".to_string(),
+            question: "Will it rain?".to_string(),
+            winning_outcome: "Yes".to_string(),
+            total_stake: Amount::from_tokens(100),
+            outcome_stakes: vec![
+                ("Yes".to_string(), Amount::from_tokens(75)),
+                ("No".to_string(), Amount::from_tokens(25)),
+            ],
+            depth: 2,
+            ancestors: vec!["m2".to_string(), "m1".to_string()],
+            resolved_at: Timestamp::from(0),
+            creators: Vec::new(),
+        }
+    }
+
+    fn eval(source: &str) -> Result<bool, EvalError> {
+        let expression = Expression::parse(source).unwrap();
+        expression.evaluate(&resolution(), Timestamp::from(0), DEFAULT_FUEL)
+    }
+
+    fn parse_error(source: &str) -> (usize, String) {
+        let ParseError { column, message } = Expression::parse(source).unwrap_err();
+        (column, message)
+    }
+
+    #[test]
+    fn operators_bind_by_precedence() {
+        assert_eq!(eval("1 + 2 * 3 == 7"), Ok(true));
+        assert_eq!(eval("(1 + 2) * 3 == 9"), Ok(true));
+        assert_eq!(eval("7 % 4 * 2 == 6"), Ok(true));
+        assert_eq!(eval("10 - 4 - 3 == 3"), Ok(true));
+        assert_eq!(eval("100 / 10 / 5 == 2"), Ok(true));
+        assert_eq!(eval("true || false && false"), Ok(true));
+        assert_eq!(eval("(true || false) && false"), Ok(false));
+        assert_eq!(eval("!false && false"), Ok(false));
+        assert_eq!(eval("depth + 1 > 2 && stake(\"Yes\") >= 75"), Ok(true));
+    }
+
+    #[test]
+    fn parse_errors_point_at_the_offending_column() {
+        assert_eq!(parse_error("depth = 1").0, 7);
+        assert_eq!(parse_error("depth & 1").0, 7);
+        assert_eq!(parse_error("depth ==").0, 9);
+        assert_eq!(parse_error("(depth > 1").0, 11);
+        assert_eq!(parse_error("depth > 1)").0, 10);
+        assert_eq!(parse_error("\"open > 1").0, 1);
+        assert_eq!(parse_error("depth > 1 && \"a\\n\" == \"\"").0, 16);
+        assert_eq!(parse_error("depth > 1 && #").0, 14);
+        assert_eq!(
+            parse_error("depth + \"x\" > 1"),
+            (7, "operator cannot be applied to integer and string".to_string())
+        );
+        assert_eq!(
+            parse_error("stake(1) > 0"),
+            (7, "`stake` expects a string, found integer".to_string())
+        );
+        assert_eq!(
+            parse_error("depth > 1 && weather > 2"),
+            (14, "unknown variable `weather`".to_string())
+        );
+        assert_eq!(
+            parse_error("depth"),
+            (1, "expression must be a boolean, found integer".to_string())
+        );
+    }
+
+    #[test]
+    fn division_by_zero_stops_evaluation() {
+        assert_eq!(eval("depth / 0 == 0"), Err(EvalError::DivisionByZero));
+        assert_eq!(eval("depth % 0 == 0"), Err(EvalError::DivisionByZero));
+        assert_eq!(eval("depth / (depth - 2) > 0"), Err(EvalError::DivisionByZero));
+        assert_eq!(eval("false && depth / 0 == 0"), Ok(false));
+    }
+
+    #[test]
+    fn arithmetic_overflow_stops_evaluation() {
+        let max = i128::MAX;
+        assert_eq!(eval(&format!("{max} + 1 > 0")), Err(EvalError::Overflow));
+        assert_eq!(eval(&format!("0 - {max} - 2 < 0")), Err(EvalError::Overflow));
+        assert_eq!(eval(&format!("{max} * 2 > 0")), Err(EvalError::Overflow));
+        assert_eq!(eval(&format!("(0 - {max} - 1) / (0 - 1) > 0")), Err(EvalError::Overflow));
+        assert_eq!(eval(&format!("{max} - 1 + 1 > 0")), Ok(true));
+        let too_large = u128::MAX;
+        assert_eq!(parse_error(&format!("{too_large} > 0")).0, 1);
+    }
+}
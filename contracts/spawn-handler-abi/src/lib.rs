@@ -0,0 +1,998 @@
+//! ABI, state and rule model of the Spawn Handler.
+//!
+//! Kept apart from the contract so the service, and the market applications
+//! notifying the handler of resolutions, can use them without linking the
+//! contract's Wasm entry points. Markets only need the ABI and build without
+//! the `rules` feature, which brings in rule evaluation and its regex engine.
+
+use fractal_abi::{
+    admin::{AdminRole, AdminSet},
+    factory::{RoyaltyRole, RoyaltyShare, TreeLimits},
+};
+use linera_sdk::base::{
+    Account, AccountOwner, Amount, ApplicationId, ChainId, TimeDelta, Timestamp,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+
+#[cfg(feature = "rules")]
+pub mod evaluation;
+pub mod expr;
+#[cfg(feature = "rules")]
+pub mod pattern;
+pub mod rule_pack;
+pub mod schedule;
+pub mod template;
+
+use rule_pack::{RuleDefinition, RulePack};
+use schedule::{Recurrence, ScheduleState};
+use template::{Template, TemplateContext};
+
+// ABI and parameters for the Spawn Handler (SDK 0.15)
+pub mod spawn_handler {
+    use super::*;
+    use linera_sdk::abi::ContractAbi;
+
+    // Operations are decoded once per block, so their size does not matter.
+    #[allow(clippy::large_enum_variant)]
+    #[derive(Debug, Deserialize, Serialize)]
+    pub enum Operation {
+        /// Only the signer who instantiated the spawn handler may initialize it.
+        Initialize { admin: AdminSet },
+        /// Admin only: proposes handing the admin role to `new_admin`, which
+        /// takes proposals from the threshold of current owners.
+        TransferAdmin { new_admin: AdminSet },
+        /// Called by an owner of the proposed set to complete a handover.
+        AcceptAdmin,
+        /// Admin only: drops a proposed handover.
+        CancelAdminTransfer,
+        CreateSpawnRule {
+            rule_id: String,
+            trigger_condition: TriggerCondition,
+            spawn_template: SpawnTemplate,
+            #[serde(default)]
+            outcome_templates: Vec<OutcomeTemplate>,
+            #[serde(default)]
+            skip_unmatched_outcomes: bool,
+            #[serde(default)]
+            settings: RuleSettings,
+            #[serde(default)]
+            scope: Option<RuleScope>,
+        },
+        UpdateSpawnRule { rule_id: String, active: bool },
+        /// Replaces the given parts of a rule's definition and bumps its version.
+        EditSpawnRule {
+            rule_id: String,
+            #[serde(default)]
+            trigger_condition: Option<TriggerCondition>,
+            #[serde(default)]
+            spawn_template: Option<SpawnTemplate>,
+            #[serde(default)]
+            outcome_templates: Option<Vec<OutcomeTemplate>>,
+            #[serde(default)]
+            skip_unmatched_outcomes: Option<bool>,
+            #[serde(default)]
+            settings: Option<RuleSettings>,
+            /// `Some(None)` removes the rule's scope.
+            #[serde(default)]
+            scope: Option<Option<RuleScope>>,
+        },
+        DeleteSpawnRule { rule_id: String },
+        TransferRuleOwnership { rule_id: String, new_owner: AccountOwner },
+        ProcessPendingSpawns,
+        /// Moves tokens from the signer into the reserve that funds spawned markets.
+        DepositReserve { amount: Amount },
+        /// Admin only: pays reserve tokens out to `recipient`.
+        WithdrawReserve { amount: Amount, recipient: Account },
+        /// Admin only: replaces the caps applied across all rules.
+        SetSpawnLimits { limits: SpawnLimits },
+        /// Admin only: moves a dead-lettered spawn back to the queue with fresh attempts.
+        RequeueSpawn { spawn_id: String },
+        /// Admin only: drops a queued or dead-lettered spawn.
+        CancelSpawn { spawn_id: String },
+        /// Admin only: replaces the bond, limits and allow-list mode for new rules.
+        SetCreationPolicy { policy: RuleCreationPolicy },
+        /// Admin only: adds `creator` to or removes it from the allow-list.
+        SetCreatorAllowed { creator: AccountOwner, allowed: bool },
+        /// Admin only: moves a rule's bond into the liquidity reserve and deletes the rule.
+        SlashRuleBond { rule_id: String },
+        /// Installs every rule of `pack`. Rules whose id exists are replaced as a
+        /// new version, which only their owner or the admin may do.
+        ImportRules { pack: RulePack },
+        /// Returns the given rules, or all of them, as a JSON rule pack.
+        ExportRules {
+            #[serde(default)]
+            rule_ids: Option<Vec<String>>,
+        },
+        /// Rule owner or admin: releases a spawn awaiting approval to the queue,
+        /// optionally replacing its generated question and outcomes.
+        ApproveSpawn {
+            spawn_id: String,
+            #[serde(default)]
+            question: Option<String>,
+            #[serde(default)]
+            outcomes: Option<Vec<String>>,
+            #[serde(default)]
+            note: Option<String>,
+        },
+        /// Rule owner or admin: drops a spawn awaiting approval.
+        RejectSpawn {
+            spawn_id: String,
+            #[serde(default)]
+            note: Option<String>,
+        },
+        /// Called on the factory chain by the market application the factory
+        /// registered for `market_id`, once it resolves. The resolution is sent
+        /// to the home chain to be evaluated against the rules.
+        NotifyResolved {
+            market_id: String,
+            winning_outcome: String,
+            outcome_stakes: Vec<(String, Amount)>,
+        },
+    }
+
+    #[derive(Debug, Clone, Deserialize, Serialize)]
+    pub struct ResponseBytes(pub Vec<u8>);
+
+    /// Argument of a new deployment; any rule pack is a valid argument.
+    #[derive(Debug, Clone, Default, Deserialize, Serialize)]
+    pub struct InstantiationArgument {
+        /// Sets the admin right away, instead of a later `Initialize`.
+        #[serde(default)]
+        pub admin: Option<AdminSet>,
+        #[serde(default)]
+        pub rules: Vec<RuleDefinition>, // Owned by the instantiating signer, or else the first admin
+    }
+
+    /// Peer applications the spawn handler talks to.
+    #[derive(Debug, Clone, Deserialize, Serialize)]
+    pub struct Parameters {
+        /// Factory application creating spawned markets.
+        pub factory_id: ApplicationId,
+        /// Chain hosting the factory.
+        pub factory_chain_id: ChainId,
+        /// Chain holding the rules and the reserve, the only one that may
+        /// request spawns from the factory chain.
+        pub home_chain_id: ChainId,
+        /// Dispatch due spawns after every incoming message, without waiting
+        /// for an explicit `ProcessPendingSpawns`.
+        pub auto_process: bool,
+    }
+
+    #[derive(Debug)]
+    pub struct SpawnHandlerAbi;
+
+    impl ContractAbi for SpawnHandlerAbi {
+        type Operation = Operation;
+        type Response = ResponseBytes;
+    }
+}
+
+/// Root state stored as a single register to avoid custom View macros
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct SpawnHandlerStateData {
+    pub spawn_rules: HashMap<String, SpawnRule>,
+    pub pending_spawns: Vec<PendingSpawn>,
+    pub spawn_count: u64, // Spawns ever queued
+    pub liquidity_reserve: Amount, // Held in the application's own account
+    pub limits: SpawnLimits,
+    pub counters: SpawnCounters,
+    pub schedules: HashMap<String, ScheduleState>, // Keyed by rule id
+    pub rule_history: Vec<RuleChange>,             // Append-only
+    pub dead_letters: Vec<DeadLetter>,
+    pub creation_policy: RuleCreationPolicy,
+    pub allowed_creators: Vec<AccountOwner>, // Consulted in allow-list mode
+    pub rule_bonds: HashMap<String, RuleBond>, // Keyed by rule id
+    pub admin: AdminRole,
+    pub spawn_ids: HashSet<String>, // Every spawn id ever queued
+}
+
+/// Conditions for creating rules, which the admin is exempt from.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RuleCreationPolicy {
+    /// Posted by the signer for every new rule, and returned when it is deleted.
+    pub bond: Amount,
+    pub max_rules_per_creator: Option<u32>,
+    /// Only signers in `allowed_creators` may create rules.
+    pub allow_list_only: bool,
+}
+
+impl Default for RuleCreationPolicy {
+    fn default() -> Self {
+        RuleCreationPolicy {
+            bond: Amount::ZERO,
+            max_rules_per_creator: Some(20),
+            allow_list_only: false,
+        }
+    }
+}
+
+/// Bond held in the application's account for as long as a rule exists.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RuleBond {
+    pub amount: Amount,
+    pub posted_by: Account, // Refunded here on deletion
+}
+
+/// Caps applied across all rules.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SpawnLimits {
+    pub max_spawns_per_resolution: Option<u32>,
+    pub max_spawns_per_day: Option<u32>,
+    pub tree: TreeLimits, // Should match the factory's, which rejects anything beyond them
+    /// Seed the children of one resolution may draw from the reserve together.
+    /// Parent stakes are reported by the market without moving tokens, so this
+    /// bounds what a single resolution can take whatever it claims.
+    pub max_seed_per_resolution: Amount,
+}
+
+impl Default for SpawnLimits {
+    fn default() -> Self {
+        SpawnLimits {
+            max_spawns_per_resolution: Some(10),
+            max_spawns_per_day: Some(1_000),
+            tree: TreeLimits::default(),
+            max_seed_per_resolution: Amount::from_tokens(100),
+        }
+    }
+}
+
+/// Spawns queued so far, for enforcing global and per-rule caps.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SpawnCounters {
+    pub day: u64, // Days since the epoch the daily counts refer to
+    pub spawned_today: u32,
+    pub rule_spawned_today: HashMap<String, u32>,
+    pub per_resolution: HashMap<String, ResolutionSpawnCount>, // Keyed by parent market id
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ResolutionSpawnCount {
+    pub total: u32,
+    pub by_rule: HashMap<String, u32>,
+    pub seed: Amount, // Drawn from the reserve by the children queued so far
+}
+
+impl SpawnCounters {
+    /// Resets the daily counts when `day` starts.
+    pub fn roll_over(&mut self, day: u64) {
+        if self.day != day {
+            self.day = day;
+            self.spawned_today = 0;
+            self.rule_spawned_today.clear();
+        }
+    }
+
+    /// Whether `rule` may queue another child of the resolved market.
+    pub fn allows(&self, limits: &SpawnLimits, rule: &SpawnRule, resolution: &Resolution) -> bool {
+        let counts = self.per_resolution.get(&resolution.market_id);
+        let resolution_total = counts.map_or(0, |count| count.total);
+        let resolution_rule = counts
+            .and_then(|count| count.by_rule.get(&rule.rule_id).copied())
+            .unwrap_or(0);
+        limits.tree.allows_child(resolution.depth, resolution_total as usize)
+            && under(resolution_total, limits.max_spawns_per_resolution)
+            && under(resolution_rule, rule.settings.max_spawns_per_resolution)
+            && self.allows_today(limits, rule)
+    }
+
+    /// Whether `rule` may queue another spawn today.
+    pub fn allows_today(&self, limits: &SpawnLimits, rule: &SpawnRule) -> bool {
+        let rule_today = self.rule_spawned_today.get(&rule.rule_id).copied().unwrap_or(0);
+        under(self.spawned_today, limits.max_spawns_per_day)
+            && under(rule_today, rule.settings.max_spawns_per_day)
+    }
+
+    /// Seed the children of `market_id` may still draw from the reserve.
+    pub fn seed_allowance(&self, limits: &SpawnLimits, market_id: &str) -> Amount {
+        let drawn = self
+            .per_resolution
+            .get(market_id)
+            .map_or(Amount::ZERO, |count| count.seed);
+        limits.max_seed_per_resolution.saturating_sub(drawn)
+    }
+
+    pub fn record(&mut self, rule_id: &str, market_id: &str, seed: Amount) {
+        self.record_today(rule_id);
+        let resolution = self.per_resolution.entry(market_id.to_string()).or_default();
+        resolution.total = resolution.total.saturating_add(1);
+        resolution.seed = resolution.seed.saturating_add(seed);
+        let resolution_rule = resolution.by_rule.entry(rule_id.to_string()).or_default();
+        *resolution_rule = resolution_rule.saturating_add(1);
+    }
+
+    pub fn record_today(&mut self, rule_id: &str) {
+        self.spawned_today = self.spawned_today.saturating_add(1);
+        let rule_today = self.rule_spawned_today.entry(rule_id.to_string()).or_default();
+        *rule_today = rule_today.saturating_add(1);
+    }
+}
+
+fn under(count: u32, cap: Option<u32>) -> bool {
+    cap.is_none_or(|cap| count < cap)
+}
+
+/// Days since the epoch, in UTC, that `timestamp` falls on; daily caps reset
+/// when it changes.
+pub fn day_index(timestamp: Timestamp) -> u64 {
+    timestamp.micros() / 86_400_000_000
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SpawnRule {
+    pub rule_id: String,
+    pub trigger_condition: TriggerCondition,
+    pub spawn_template: SpawnTemplate, // Default when no outcome template applies
+    pub outcome_templates: Vec<OutcomeTemplate>, // Checked in order
+    pub skip_unmatched_outcomes: bool, // Spawn nothing instead of using the default
+    pub settings: RuleSettings,
+    pub scope: Option<RuleScope>, // None applies the rule to every resolution
+    pub active: bool,
+    pub created_by: AccountOwner, // Signer owning the rule, changed by `TransferRuleOwnership`
+    pub version: u32,        // Bumped by every edit of the definition
+}
+
+/// One entry of the rule history, with the rule as it was after the change.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RuleChange {
+    pub rule_id: String,
+    pub version: u32,
+    pub change: RuleChangeKind,
+    pub changed_by: AccountOwner,
+    pub changed_at: Timestamp,
+    pub rule: SpawnRule,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum RuleChangeKind {
+    Created,
+    Edited,
+    Activated,
+    Deactivated,
+    OwnershipTransferred { from: AccountOwner, to: AccountOwner },
+    Deleted,
+    /// Deleted by the admin, with the bond moved into the liquidity reserve.
+    Slashed { bond: Amount },
+}
+
+/// Restricts a rule to resolutions of one market and, optionally, its descendants.
+/// Only counts for rules owned by the market's creator or by an admin.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RuleScope {
+    pub market_id: String,
+    pub include_descendants: bool,
+}
+
+impl RuleScope {
+    pub fn contains(&self, resolution: &Resolution) -> bool {
+        resolution.market_id == self.market_id
+            || (self.include_descendants && resolution.ancestors.contains(&self.market_id))
+    }
+}
+
+/// Evaluation order and per-rule caps. Omitted fields take their defaults.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RuleSettings {
+    pub priority: i32,   // Higher priorities are evaluated first
+    pub exclusive: bool, // Stop evaluating further rules once this one matches
+    pub max_spawns_per_resolution: Option<u32>,
+    pub max_spawns_per_day: Option<u32>,
+    /// Hold generated spawns until the rule owner or admin approves them.
+    pub requires_approval: bool,
+    pub royalties: RoyaltyConfig,
+}
+
+/// Shares of a spawned market's fees, in basis points, paid to the rule's
+/// owner and to the creator of the parent market.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RoyaltyConfig {
+    pub rule_author_basis_points: u32,
+    pub parent_creator_basis_points: u32,
+    /// Fee spawned markets take from every bet, out of which the shares are paid.
+    #[serde(default)]
+    pub trading_fee_basis_points: u32,
+    /// Chain the rule owner's royalties are paid on, and where it owns the
+    /// spawned markets; the handler's chain by default.
+    #[serde(default)]
+    pub payout_chain_id: Option<ChainId>,
+}
+
+impl RoyaltyConfig {
+    /// Whether the fee and the shares each claim no more than the whole.
+    pub fn fits(&self) -> bool {
+        self.trading_fee_basis_points <= 10_000
+            && self
+                .rule_author_basis_points
+                .checked_add(self.parent_creator_basis_points)
+                .is_some_and(|total| total <= 10_000)
+    }
+
+    /// Shares owed by a child spawned by a rule of `author`. The factory fills
+    /// in the parent's creator, and drops that share for root markets.
+    pub fn shares(&self, author: Account) -> Vec<RoyaltyShare> {
+        let rule_author = RoyaltyShare {
+            role: RoyaltyRole::RuleAuthor,
+            recipient: Some(author),
+            basis_points: self.rule_author_basis_points,
+        };
+        let parent_creator = RoyaltyShare {
+            role: RoyaltyRole::ParentCreator,
+            recipient: None,
+            basis_points: self.parent_creator_basis_points,
+        };
+        [rule_author, parent_creator]
+            .into_iter()
+            .filter(|share| share.basis_points > 0)
+            .collect()
+    }
+}
+
+/// Template used when the parent's winning outcome matches `selector`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct OutcomeTemplate {
+    pub selector: OutcomeSelector,
+    pub spawn_template: SpawnTemplate,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum OutcomeSelector {
+    Exact { outcome: String },
+    Pattern { pattern: String }, // Same regex dialect as trigger patterns
+}
+
+#[cfg(feature = "rules")]
+impl SpawnRule {
+    pub fn validate(&self) -> Result<(), SpawnHandlerError> {
+        validate_definition(
+            &self.trigger_condition,
+            &self.spawn_template,
+            &self.outcome_templates,
+            &self.settings,
+            self.scope.as_ref(),
+        )
+    }
+}
+
+/// Checks the parts of a rule its author writes, shared by installed rules
+/// and the definitions in a pack.
+#[cfg(feature = "rules")]
+pub(crate) fn validate_definition(
+    trigger_condition: &TriggerCondition,
+    spawn_template: &SpawnTemplate,
+    outcome_templates: &[OutcomeTemplate],
+    settings: &RuleSettings,
+    scope: Option<&RuleScope>,
+) -> Result<(), SpawnHandlerError> {
+    trigger_condition.validate()?;
+    if scope.is_some() && matches!(trigger_condition, TriggerCondition::Schedule { .. }) {
+        return Err(SpawnHandlerError::InvalidCondition(
+            "scheduled rules cannot be scoped to a market".to_string(),
+        ));
+    }
+    if !settings.royalties.fits() {
+        return Err(SpawnHandlerError::InvalidSettings(
+            "fees or royalties exceed 10000 basis points".to_string(),
+        ));
+    }
+    spawn_template.validate()?;
+    for outcome_template in outcome_templates {
+        if let OutcomeSelector::Pattern { pattern } = &outcome_template.selector {
+            pattern::compile(pattern)?;
+        }
+        outcome_template.spawn_template.validate()?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum TriggerCondition {
+    MarketResolution {
+        market_pattern: String, // Regex pattern for market questions
+        outcome_pattern: String, // Regex pattern for outcomes
+    },
+    /// Spawns `delay_seconds` after the parent resolved, optionally only for
+    /// resolutions matching the given patterns. Nested in `All` or `Any`, the
+    /// longest delay among the branches that matched applies.
+    TimeDelay {
+        delay_seconds: u64,
+        #[serde(default)]
+        market_pattern: Option<String>,
+        #[serde(default)]
+        outcome_pattern: Option<String>,
+    },
+    /// Boolean expression over the resolution, see the `expr` module.
+    CustomLogic {
+        expression: String,
+    },
+    /// Matches when every condition matches.
+    All {
+        conditions: Vec<TriggerCondition>,
+    },
+    /// Matches when at least one condition matches.
+    Any {
+        conditions: Vec<TriggerCondition>,
+    },
+    Not {
+        condition: Box<TriggerCondition>,
+    },
+    /// Matches when strictly more than `amount` was staked on the parent.
+    TotalStakeAbove {
+        amount: Amount,
+    },
+    WinnerIn {
+        outcomes: Vec<String>,
+    },
+    /// Matches when the resolved market is `root_market_id` or one of its descendants.
+    ParentInSubtree {
+        root_market_id: String,
+    },
+    /// Matches when the resolved market is fewer than `max_depth` levels below the root.
+    DepthBelow {
+        max_depth: u32,
+    },
+    /// Fires on every tick of `recurrence` from `start_time` until `end_time` or
+    /// `max_occurrences`, independently of resolutions. Ticks are picked up by
+    /// `ProcessPendingSpawns`; only valid at the top level of a rule.
+    Schedule {
+        recurrence: Recurrence,
+        start_time: Timestamp,
+        #[serde(default)]
+        end_time: Option<Timestamp>,
+        #[serde(default)]
+        max_occurrences: Option<u32>,
+    },
+}
+
+/// Deepest nesting of `All`, `Any` and `Not` accepted in a rule.
+#[cfg(feature = "rules")]
+const MAX_CONDITION_DEPTH: usize = 8;
+
+#[cfg(feature = "rules")]
+impl TriggerCondition {
+    /// Checks that every pattern and expression in the condition compiles.
+    pub fn validate(&self) -> Result<(), SpawnHandlerError> {
+        self.validate_nested(0)
+    }
+
+    fn validate_nested(&self, depth: usize) -> Result<(), SpawnHandlerError> {
+        if depth > MAX_CONDITION_DEPTH {
+            return Err(SpawnHandlerError::InvalidCondition(format!(
+                "conditions are nested more than {MAX_CONDITION_DEPTH} levels deep"
+            )));
+        }
+        match self {
+            TriggerCondition::MarketResolution {
+                market_pattern,
+                outcome_pattern,
+            } => {
+                pattern::compile(market_pattern)?;
+                pattern::compile(outcome_pattern)?;
+            }
+            TriggerCondition::TimeDelay {
+                market_pattern,
+                outcome_pattern,
+                ..
+            } => {
+                for pattern in market_pattern.iter().chain(outcome_pattern) {
+                    pattern::compile(pattern)?;
+                }
+            }
+            TriggerCondition::CustomLogic { expression } => {
+                expr::Expression::parse(expression)
+                    .map_err(|error| SpawnHandlerError::InvalidExpression(error.to_string()))?;
+            }
+            TriggerCondition::All { conditions } | TriggerCondition::Any { conditions } => {
+                for condition in conditions {
+                    condition.validate_nested(depth + 1)?;
+                }
+            }
+            TriggerCondition::Not { condition } => condition.validate_nested(depth + 1)?,
+            TriggerCondition::WinnerIn { outcomes } if outcomes.is_empty() => {
+                return Err(SpawnHandlerError::InvalidCondition(
+                    "`WinnerIn` needs at least one outcome".to_string(),
+                ));
+            }
+            TriggerCondition::Schedule { .. } if depth > 0 => {
+                return Err(SpawnHandlerError::InvalidCondition(
+                    "`Schedule` cannot be combined with other conditions".to_string(),
+                ));
+            }
+            TriggerCondition::Schedule {
+                start_time,
+                end_time: Some(end_time),
+                ..
+            } if end_time < start_time => {
+                return Err(SpawnHandlerError::InvalidCondition(
+                    "schedule ends before it starts".to_string(),
+                ));
+            }
+            TriggerCondition::Schedule { recurrence, .. } => recurrence.validate()?,
+            TriggerCondition::TotalStakeAbove { .. }
+            | TriggerCondition::WinnerIn { .. }
+            | TriggerCondition::ParentInSubtree { .. }
+            | TriggerCondition::DepthBelow { .. } => {}
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SpawnTemplate {
+    pub question_template: String, // Template with placeholders like {parent_question}, {outcome}
+    pub outcomes: Vec<String>,     // Each outcome is a template too
+    pub expiry_offset_seconds: u64,
+    pub seed_liquidity: SeedLiquidity,
+}
+
+/// Liquidity a spawned child is seeded with from the handler's reserve.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum SeedLiquidity {
+    /// Share of the parent's total stake in basis points, clamped to `[min, max]`.
+    /// Stakes are not backed by tokens the handler can check, so `max` is required.
+    Ratio {
+        basis_points: u32,
+        #[serde(default)]
+        min: Option<Amount>,
+        max: Amount,
+    },
+    Absolute {
+        amount: Amount,
+    },
+}
+
+impl SeedLiquidity {
+    pub fn validate(&self) -> Result<(), SpawnHandlerError> {
+        match self {
+            SeedLiquidity::Ratio { basis_points, .. } if *basis_points > 10_000 => Err(
+                SpawnHandlerError::InvalidTemplate("seed ratio exceeds 10000 basis points".to_string()),
+            ),
+            SeedLiquidity::Ratio {
+                min: Some(min),
+                max,
+                ..
+            } if min > max => Err(SpawnHandlerError::InvalidTemplate(
+                "minimum seed liquidity exceeds the maximum".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Seed for a child whose parent had `parent_total_stake` staked.
+    pub fn amount(&self, parent_total_stake: Amount) -> Amount {
+        match self {
+            SeedLiquidity::Ratio {
+                basis_points,
+                min,
+                max,
+            } => {
+                let attos = u128::from(parent_total_stake).saturating_mul(u128::from(*basis_points))
+                    / 10_000;
+                let seed = Amount::from_attos(attos);
+                min.map_or(seed, |min| seed.max(min)).min(*max)
+            }
+            SeedLiquidity::Absolute { amount } => *amount,
+        }
+    }
+}
+
+impl SpawnTemplate {
+    /// Checks that the question and every outcome parse as templates.
+    pub fn validate(&self) -> Result<(), SpawnHandlerError> {
+        if self.outcomes.len() < 2 {
+            return Err(SpawnHandlerError::InvalidTemplate(
+                "a spawned market needs at least two outcomes".to_string(),
+            ));
+        }
+        self.seed_liquidity.validate()?;
+        for source in std::iter::once(&self.question_template).chain(&self.outcomes) {
+            Template::parse(source)?;
+        }
+        Ok(())
+    }
+
+    /// Renders the child's question and outcomes, or `None` if a template
+    /// no longer parses.
+    pub fn render(&self, context: &TemplateContext) -> Option<(String, Vec<String>)> {
+        let question = Template::parse(&self.question_template).ok()?.render(context);
+        let outcomes = self
+            .outcomes
+            .iter()
+            .map(|source| Some(Template::parse(source).ok()?.render(context)))
+            .collect::<Option<Vec<_>>>()?;
+        Some((question, outcomes))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PendingSpawn {
+    pub spawn_id: String, // Derived by the `ids` module, unique across the handler's lifetime
+    pub rule_id: String,
+    pub parent_market_id: Option<String>, // None for scheduled spawns
+    pub parent_outcome: String,           // Empty for scheduled spawns
+    pub parent_total_stake: Amount,
+    pub question: String, // Rendered from the template
+    pub outcomes: Vec<String>,
+    pub seed_liquidity: Amount,
+    pub creator: Account,             // Rule author, recorded as the market's creator
+    pub trading_fee_basis_points: u32,
+    pub royalties: Vec<RoyaltyShare>, // Fixed when queued, passed on to the factory
+    pub spawn_template: SpawnTemplate,
+    pub scheduled_time: Timestamp,
+    pub status: SpawnStatus,
+    pub attempts: u32, // Dispatches so far
+    pub last_dispatched: Option<Timestamp>,
+    pub retry_at: Option<Timestamp>, // Earliest next dispatch after a failed attempt
+    pub seed_escrowed: bool,         // Seed held back from the reserve until the market exists
+    pub market_id: Option<String>,   // Market created for the spawn, once confirmed
+    pub review: Option<SpawnReview>, // Set once a spawn awaiting approval is decided
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SpawnStatus {
+    /// Held for the rule owner or admin; never dispatched until approved.
+    AwaitingApproval,
+    /// Waiting for its scheduled time, or for a retry.
+    Queued,
+    /// `SpawnMarket` sent to the factory chain, awaiting `SpawnConfirmed`.
+    Dispatched,
+    Confirmed,
+    /// Out of attempts; listed in the dead letters until requeued or cancelled.
+    Failed,
+    Cancelled,
+    Rejected,
+}
+
+impl PendingSpawn {
+    /// Releases the escrowed seed, returning the amount owed back to the reserve.
+    pub fn release_seed(&mut self) -> Amount {
+        if std::mem::take(&mut self.seed_escrowed) {
+            self.seed_liquidity
+        } else {
+            Amount::ZERO
+        }
+    }
+}
+
+/// Decision on a spawn whose rule requires approval.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SpawnReview {
+    pub decision: ReviewDecision,
+    pub reviewed_by: AccountOwner,
+    pub reviewed_at: Timestamp,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum ReviewDecision {
+    Approved,
+    /// Approved after replacing the generated question or outcomes, kept here.
+    Edited {
+        original_question: String,
+        original_outcomes: Vec<String>,
+    },
+    Rejected,
+}
+
+/// A spawn that exhausted its dispatch attempts.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DeadLetter {
+    pub spawn_id: String,
+    pub reason: String, // Why the last attempt failed
+    pub failed_at: Timestamp,
+}
+
+/// Attempts at delivering a spawn before it is dead-lettered.
+pub const MAX_DISPATCH_ATTEMPTS: u32 = 5;
+/// Delay before the first retry, doubled for every further attempt.
+pub const RETRY_BASE_DELAY_SECONDS: u64 = 60;
+/// How long a dispatched spawn may go unconfirmed before it is retried.
+pub const CONFIRMATION_TIMEOUT_SECONDS: u64 = 10 * 60;
+
+impl SpawnHandlerStateData {
+    /// Records a failed dispatch of `spawn_id`: queues a retry with exponential
+    /// backoff, or dead-letters the spawn once it is out of attempts and
+    /// returns its seed to the reserve.
+    pub fn fail_dispatch(&mut self, spawn_id: &str, reason: &str, now: Timestamp) {
+        let Some(spawn) = self
+            .pending_spawns
+            .iter_mut()
+            .find(|spawn| spawn.spawn_id == spawn_id && spawn.status == SpawnStatus::Dispatched)
+        else {
+            return;
+        };
+        if spawn.attempts >= MAX_DISPATCH_ATTEMPTS {
+            spawn.status = SpawnStatus::Failed;
+            self.liquidity_reserve = self.liquidity_reserve.saturating_add(spawn.release_seed());
+            self.dead_letters.push(DeadLetter {
+                spawn_id: spawn_id.to_string(),
+                reason: reason.to_string(),
+                failed_at: now,
+            });
+        } else {
+            let backoff = RETRY_BASE_DELAY_SECONDS << spawn.attempts.saturating_sub(1);
+            spawn.status = SpawnStatus::Queued;
+            spawn.retry_at = Some(now.saturating_add(TimeDelta::from_secs(backoff)));
+        }
+    }
+}
+
+/// Facts about a resolved market that spawn rules are evaluated against.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Resolution {
+    pub market_id: String,
+    pub question: String,
+    pub winning_outcome: String,
+    pub total_stake: Amount,
+    pub outcome_stakes: Vec<(String, Amount)>,
+    pub depth: u32, // Distance from the root of the market tree
+    pub ancestors: Vec<String>, // Nearest parent first
+    pub resolved_at: Timestamp,
+    /// Creators of the market and its ancestors, as registered by the factory.
+    #[serde(default)]
+    pub creators: Vec<(String, AccountOwner)>,
+}
+
+/// Amount as a whole number of tokens, rounded down.
+pub(crate) fn whole_tokens(amount: Amount) -> u128 {
+    u128::from(amount) / u128::from(Amount::ONE)
+}
+
+impl Resolution {
+    /// Stand-in rendered by scheduled rules, which have no parent: every
+    /// parent placeholder is empty and `resolution_date` is the tick.
+    pub fn scheduled(tick: Timestamp) -> Self {
+        Resolution {
+            market_id: String::new(),
+            question: String::new(),
+            winning_outcome: String::new(),
+            total_stake: Amount::ZERO,
+            outcome_stakes: Vec::new(),
+            depth: 0,
+            ancestors: Vec::new(),
+            resolved_at: tick,
+            creators: Vec::new(),
+        }
+    }
+
+    /// Creator of `market_id`, if it is the resolved market or one of its ancestors.
+    pub fn creator_of(&self, market_id: &str) -> Option<AccountOwner> {
+        self.creators
+            .iter()
+            .find(|(id, _)| id == market_id)
+            .map(|(_, creator)| *creator)
+    }
+
+    /// Total staked on `outcome`, zero if unknown.
+    pub fn outcome_stake(&self, outcome: &str) -> Amount {
+        self.outcome_stakes
+            .iter()
+            .find(|(name, _)| name == outcome)
+            .map_or(Amount::ZERO, |(_, stake)| *stake)
+    }
+}
+
+/// Spawn handler errors
+#[derive(Debug, Error)]
+pub enum SpawnHandlerError {
+    #[error("Unauthorized")]
+    Unauthorized,
+    #[error("Rule not found")]
+    RuleNotFound,
+    #[error("Rule already exists")]
+    RuleAlreadyExists,
+    #[error("Invalid template: {0}")]
+    InvalidTemplate(String),
+    #[error("Invalid pattern: {0}")]
+    InvalidPattern(String),
+    #[error("Invalid expression: {0}")]
+    InvalidExpression(String),
+    #[error("Invalid condition: {0}")]
+    InvalidCondition(String),
+    #[error("Processing failed")]
+    ProcessingFailed,
+    #[error("Insufficient liquidity reserve")]
+    InsufficientReserve,
+    #[error("Market not found")]
+    MarketNotFound,
+    #[error("Spawn not found")]
+    SpawnNotFound,
+    #[error("Invalid spawn: {0}")]
+    InvalidSpawn(String),
+    #[error("Invalid rule pack: {0}")]
+    InvalidRulePack(String),
+    #[error("Rule limit reached")]
+    RuleLimitReached,
+    #[error("Invalid settings: {0}")]
+    InvalidSettings(String),
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending_spawn(spawn_id: &str, seed_liquidity: Amount) -> PendingSpawn {
+        PendingSpawn {
+            spawn_id: spawn_id.to_string(),
+            rule_id: "r1".to_string(),
+            parent_market_id: Some("m1".to_string()),
+            parent_outcome: "Yes".to_string(),
+            parent_total_stake: Amount::ZERO,
+            question: "What next?".to_string(),
+            outcomes: vec!["Yes".to_string(), "No".to_string()],
+            seed_liquidity,
+            creator: Account {
+                chain_id: ChainId::root(0),
+                owner: AccountOwner::Address20([1; 20]),
+            },
+            trading_fee_basis_points: 0,
+            royalties: Vec::new(),
+            spawn_template: SpawnTemplate {
+                question_template: "What next?".to_string(),
+                outcomes: vec!["Yes".to_string(), "No".to_string()],
+                expiry_offset_seconds: 3_600,
+                seed_liquidity: SeedLiquidity::Absolute {
+                    amount: seed_liquidity,
+                },
+            },
+            scheduled_time: Timestamp::from(0),
+            status: SpawnStatus::Dispatched,
+            attempts: 0,
+            last_dispatched: None,
+            retry_at: None,
+            seed_escrowed: true,
+            market_id: None,
+            review: None,
+        }
+    }
+
+    #[test]
+    fn retries_with_exponential_backoff() {
+        let mut data = SpawnHandlerStateData::default();
+        data.pending_spawns.push(pending_spawn("s1", Amount::from_tokens(3)));
+        let now = Timestamp::from(1_000_000);
+        for (attempts, backoff_seconds) in (1..MAX_DISPATCH_ATTEMPTS).zip([60, 120, 240, 480]) {
+            let spawn = &mut data.pending_spawns[0];
+            spawn.status = SpawnStatus::Dispatched;
+            spawn.attempts = attempts;
+            data.fail_dispatch("s1", "timed out", now);
+            let spawn = &data.pending_spawns[0];
+            assert_eq!(spawn.status, SpawnStatus::Queued);
+            assert_eq!(
+                spawn.retry_at,
+                Some(now.saturating_add(TimeDelta::from_secs(backoff_seconds)))
+            );
+            assert!(spawn.seed_escrowed);
+        }
+        assert!(data.dead_letters.is_empty());
+        assert_eq!(data.liquidity_reserve, Amount::ZERO);
+    }
+
+    #[test]
+    fn dead_letters_after_the_last_attempt_and_refunds_the_seed() {
+        let mut data = SpawnHandlerStateData {
+            liquidity_reserve: Amount::from_tokens(10),
+            ..SpawnHandlerStateData::default()
+        };
+        data.pending_spawns.push(pending_spawn("s1", Amount::from_tokens(3)));
+        data.pending_spawns[0].attempts = MAX_DISPATCH_ATTEMPTS;
+        let now = Timestamp::from(1_000_000);
+        data.fail_dispatch("s1", "rejected by the factory", now);
+
+        let spawn = &data.pending_spawns[0];
+        assert_eq!(spawn.status, SpawnStatus::Failed);
+        assert!(!spawn.seed_escrowed);
+        assert_eq!(data.liquidity_reserve, Amount::from_tokens(13));
+        assert_eq!(data.dead_letters.len(), 1);
+        assert_eq!(data.dead_letters[0].spawn_id, "s1");
+        assert_eq!(data.dead_letters[0].reason, "rejected by the factory");
+        assert_eq!(data.dead_letters[0].failed_at, now);
+
+        // Spawns no longer dispatched are left alone, so the seed is refunded once.
+        data.fail_dispatch("s1", "rejected by the factory", now);
+        assert_eq!(data.liquidity_reserve, Amount::from_tokens(13));
+        assert_eq!(data.dead_letters.len(), 1);
+    }
+}
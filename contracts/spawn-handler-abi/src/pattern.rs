@@ -0,0 +1,87 @@
+//! Regular expressions used by spawn rule trigger patterns.
+
+use regex::{Regex, RegexBuilder};
+use std::collections::HashMap;
+
+use crate::SpawnHandlerError;
+
+/// Longest pattern accepted in a spawn rule.
+pub const MAX_PATTERN_LENGTH: usize = 256;
+
+/// Upper bound on the compiled program size, keeping matching cheap in Wasm.
+const COMPILED_SIZE_LIMIT: usize = 1 << 18;
+
+/// Compiles a trigger pattern. Patterns are unanchored and case-insensitive,
+/// so `election` matches "Who wins the Election?".
+pub fn compile(pattern: &str) -> Result<Regex, SpawnHandlerError> {
+    if pattern.len() > MAX_PATTERN_LENGTH {
+        return Err(SpawnHandlerError::InvalidPattern(format!(
+            "pattern is longer than {MAX_PATTERN_LENGTH} bytes"
+        )));
+    }
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .size_limit(COMPILED_SIZE_LIMIT)
+        .build()
+        .map_err(|error| SpawnHandlerError::InvalidPattern(error.to_string()))
+}
+
+/// Compiled patterns, kept while the contract is loaded so each pattern is
+/// compiled at most once per block.
+#[derive(Debug, Default)]
+pub struct PatternCache {
+    compiled: HashMap<String, Regex>,
+}
+
+impl PatternCache {
+    /// Returns whether `pattern` matches `haystack`. Patterns that fail to
+    /// compile never match; they are rejected when rules are created.
+    pub fn is_match(&mut self, pattern: &str, haystack: &str) -> bool {
+        if !self.compiled.contains_key(pattern) {
+            let Ok(regex) = compile(pattern) else {
+                return false;
+            };
+            self.compiled.insert(pattern.to_string(), regex);
+        }
+        self.compiled[pattern].is_match(haystack)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TriggerCondition;
+
+    #[test]
+    fn matches_alternatives_case_insensitively() {
+        let regex = compile(".*election.*|.*vote.*").unwrap();
+        assert!(regex.is_match("Who wins the Election?"));
+        assert!(regex.is_match("Will the VOTE pass?"));
+        assert!(!regex.is_match("Who wins the match?"));
+    }
+
+    #[test]
+    fn rejects_invalid_and_long_patterns() {
+        assert!(matches!(compile("(unclosed"), Err(SpawnHandlerError::InvalidPattern(_))));
+        assert!(compile(&"a".repeat(MAX_PATTERN_LENGTH)).is_ok());
+        let long = "a".repeat(MAX_PATTERN_LENGTH + 1);
+        assert!(matches!(compile(&long), Err(SpawnHandlerError::InvalidPattern(_))));
+        let condition = TriggerCondition::MarketResolution {
+            market_pattern: long,
+            outcome_pattern: ".*".to_string(),
+        };
+        assert!(matches!(condition.validate(), Err(SpawnHandlerError::InvalidPattern(_))));
+    }
+
+    #[test]
+    fn caches_compiled_patterns() {
+        let mut cache = PatternCache::default();
+        assert!(cache.is_match("election", "Who wins the Election?"));
+        assert!(!cache.is_match("election", "Who wins the match?"));
+        assert_eq!(cache.compiled.len(), 1);
+        assert!(cache.compiled["election"].is_match("ELECTION"));
+        // Invalid patterns never match and are not kept.
+        assert!(!cache.is_match("(unclosed", "(unclosed"));
+        assert_eq!(cache.compiled.len(), 1);
+    }
+}
@@ -0,0 +1,118 @@
+//! Declarative sets of spawn rules.
+//!
+//! A pack lists rule definitions without ownership or version, so the same
+//! file can be kept in a repository, passed as the instantiation argument of a
+//! new deployment, or imported into a running one. Packs are written as JSON;
+//! the service also reads and writes them as TOML.
+//! `spawn-handler/rule-packs/default.json` holds the rules earlier deployments
+//! installed on `Initialize`.
+
+use linera_sdk::base::AccountOwner;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    OutcomeTemplate, RuleScope, RuleSettings, SpawnHandlerError, SpawnRule, SpawnTemplate,
+    TriggerCondition,
+};
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RulePack {
+    #[serde(default)]
+    pub rules: Vec<RuleDefinition>,
+}
+
+/// A spawn rule as written in a pack.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RuleDefinition {
+    pub rule_id: String,
+    pub trigger_condition: TriggerCondition,
+    pub spawn_template: SpawnTemplate,
+    #[serde(default)]
+    pub outcome_templates: Vec<OutcomeTemplate>,
+    #[serde(default)]
+    pub skip_unmatched_outcomes: bool,
+    #[serde(default)]
+    pub settings: RuleSettings,
+    #[serde(default)]
+    pub scope: Option<RuleScope>,
+    #[serde(default = "active_by_default")]
+    pub active: bool,
+}
+
+fn active_by_default() -> bool {
+    true
+}
+
+impl RuleDefinition {
+    pub fn into_rule(self, created_by: AccountOwner, version: u32) -> SpawnRule {
+        SpawnRule {
+            rule_id: self.rule_id,
+            trigger_condition: self.trigger_condition,
+            spawn_template: self.spawn_template,
+            outcome_templates: self.outcome_templates,
+            skip_unmatched_outcomes: self.skip_unmatched_outcomes,
+            settings: self.settings,
+            scope: self.scope,
+            active: self.active,
+            created_by,
+            version,
+        }
+    }
+
+    /// Checks the definition as [`SpawnRule::validate`] would once installed.
+    #[cfg(feature = "rules")]
+    pub fn validate(&self) -> Result<(), SpawnHandlerError> {
+        crate::validate_definition(
+            &self.trigger_condition,
+            &self.spawn_template,
+            &self.outcome_templates,
+            &self.settings,
+            self.scope.as_ref(),
+        )
+    }
+}
+
+impl From<&SpawnRule> for RuleDefinition {
+    fn from(rule: &SpawnRule) -> Self {
+        RuleDefinition {
+            rule_id: rule.rule_id.clone(),
+            trigger_condition: rule.trigger_condition.clone(),
+            spawn_template: rule.spawn_template.clone(),
+            outcome_templates: rule.outcome_templates.clone(),
+            skip_unmatched_outcomes: rule.skip_unmatched_outcomes,
+            settings: rule.settings.clone(),
+            scope: rule.scope.clone(),
+            active: rule.active,
+        }
+    }
+}
+
+impl RulePack {
+    /// Pack of `rules`, sorted by id so exports are stable.
+    pub fn from_rules<'a>(rules: impl IntoIterator<Item = &'a SpawnRule>) -> Self {
+        let mut rules = rules.into_iter().map(RuleDefinition::from).collect::<Vec<_>>();
+        rules.sort_by(|a, b| a.rule_id.cmp(&b.rule_id));
+        RulePack { rules }
+    }
+
+    /// Checks that no rule id is defined twice and that every rule's
+    /// patterns, expressions and templates are valid. Scopes are checked
+    /// against the registry as the rules are installed.
+    #[cfg(feature = "rules")]
+    pub fn validate(&self) -> Result<(), SpawnHandlerError> {
+        let mut rule_ids = self.rules.iter().map(|rule| &rule.rule_id).collect::<Vec<_>>();
+        rule_ids.sort();
+        if let Some(pair) = rule_ids.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(SpawnHandlerError::InvalidRulePack(format!(
+                "rule {} is defined twice",
+                pair[0]
+            )));
+        }
+        for rule in &self.rules {
+            rule.validate().map_err(|error| {
+                SpawnHandlerError::InvalidRulePack(format!("rule {}: {error}", rule.rule_id))
+            })?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+//! Recurrence of `TriggerCondition::Schedule` rules.
+//!
+//! Every recurrence is a fixed period with an offset from the Unix epoch, so
+//! the latest tick before any time is found without iterating.
+
+use linera_sdk::base::Timestamp;
+use serde::{Deserialize, Serialize};
+
+use crate::SpawnHandlerError;
+
+const MICROS_PER_MINUTE: u64 = 60_000_000;
+const MICROS_PER_DAY: u64 = 24 * 60 * MICROS_PER_MINUTE;
+
+/// Shortest interval accepted between two ticks.
+pub const MIN_INTERVAL_SECONDS: u64 = 60;
+
+/// When a scheduled rule fires. Calendar times are UTC.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum Recurrence {
+    /// Every `seconds`, starting at the schedule's start time.
+    Interval { seconds: u64 },
+    Daily { hour: u8, minute: u8 },
+    /// `weekday` counts from Monday = 0.
+    Weekly { weekday: u8, hour: u8, minute: u8 },
+}
+
+/// Firing history of a scheduled rule.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ScheduleState {
+    pub last_fired: Option<Timestamp>,
+    pub occurrences: u32,
+}
+
+impl Recurrence {
+    pub fn validate(&self) -> Result<(), SpawnHandlerError> {
+        let invalid = |message: &str| Err(SpawnHandlerError::InvalidCondition(message.to_string()));
+        match self {
+            Recurrence::Interval { seconds } if *seconds < MIN_INTERVAL_SECONDS => {
+                invalid("schedule interval is shorter than a minute")
+            }
+            Recurrence::Daily { hour, minute } | Recurrence::Weekly { hour, minute, .. }
+                if *hour >= 24 || *minute >= 60 =>
+            {
+                invalid("schedule time of day is out of range")
+            }
+            Recurrence::Weekly { weekday, .. } if *weekday >= 7 => {
+                invalid("schedule weekday is out of range")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Period and offset from the epoch of the ticks, in microseconds.
+    fn period_and_offset(&self, start_time: Timestamp) -> (u64, u64) {
+        let time_of_day =
+            |hour: u8, minute: u8| (u64::from(hour) * 60 + u64::from(minute)) * MICROS_PER_MINUTE;
+        match self {
+            Recurrence::Interval { seconds } => {
+                let period = seconds.saturating_mul(1_000_000).max(1);
+                (period, start_time.micros() % period)
+            }
+            Recurrence::Daily { hour, minute } => (MICROS_PER_DAY, time_of_day(*hour, *minute)),
+            Recurrence::Weekly {
+                weekday,
+                hour,
+                minute,
+            } => {
+                // The epoch was a Thursday, weekday 3.
+                let day = (u64::from(*weekday) + 4) % 7;
+                (7 * MICROS_PER_DAY, day * MICROS_PER_DAY + time_of_day(*hour, *minute))
+            }
+        }
+    }
+
+    /// Latest tick at or after `start_time` and at or before `until`.
+    pub fn latest_tick(&self, start_time: Timestamp, until: Timestamp) -> Option<Timestamp> {
+        let (period, offset) = self.period_and_offset(start_time);
+        let until = until.micros();
+        if until < offset {
+            return None;
+        }
+        let tick = until - (until - offset) % period;
+        (tick >= start_time.micros()).then(|| Timestamp::from_micros(tick))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MICROS_PER_HOUR: u64 = 60 * MICROS_PER_MINUTE;
+
+    /// 2024-03-01, a Friday.
+    const FRIDAY: u64 = 19_783;
+
+    /// `hour:minute` UTC on the day `days` after the epoch.
+    fn at(days: u64, hour: u64, minute: u64) -> Timestamp {
+        Timestamp::from_micros(
+            days * MICROS_PER_DAY + hour * MICROS_PER_HOUR + minute * MICROS_PER_MINUTE,
+        )
+    }
+
+    #[test]
+    fn intervals_fire_every_period_from_the_start() {
+        let hourly = Recurrence::Interval { seconds: 3_600 };
+        let start = at(FRIDAY, 10, 30);
+        assert_eq!(hourly.latest_tick(start, at(FRIDAY, 10, 29)), None);
+        assert_eq!(hourly.latest_tick(start, start), Some(start));
+        assert_eq!(hourly.latest_tick(start, at(FRIDAY, 11, 29)), Some(start));
+        assert_eq!(hourly.latest_tick(start, at(FRIDAY, 12, 45)), Some(at(FRIDAY, 12, 30)));
+        assert_eq!(hourly.latest_tick(start, at(FRIDAY + 1, 0, 0)), Some(at(FRIDAY, 23, 30)));
+    }
+
+    #[test]
+    fn daily_schedules_fire_at_the_time_of_day() {
+        let daily = Recurrence::Daily { hour: 9, minute: 0 };
+        let start = at(FRIDAY, 0, 0);
+        assert_eq!(daily.latest_tick(start, at(FRIDAY, 8, 59)), None);
+        assert_eq!(daily.latest_tick(start, at(FRIDAY, 9, 0)), Some(at(FRIDAY, 9, 0)));
+        assert_eq!(daily.latest_tick(start, at(FRIDAY + 1, 8, 0)), Some(at(FRIDAY, 9, 0)));
+        assert_eq!(daily.latest_tick(start, at(FRIDAY + 3, 23, 0)), Some(at(FRIDAY + 3, 9, 0)));
+        // A start after the time of day skips that day's tick.
+        let late_start = at(FRIDAY, 9, 1);
+        assert_eq!(daily.latest_tick(late_start, at(FRIDAY, 23, 0)), None);
+        assert_eq!(daily.latest_tick(late_start, at(FRIDAY + 1, 9, 0)), Some(at(FRIDAY + 1, 9, 0)));
+    }
+
+    #[test]
+    fn weekly_schedules_count_weekdays_from_monday() {
+        let monday = Recurrence::Weekly { weekday: 0, hour: 8, minute: 0 };
+        let start = at(FRIDAY, 0, 0);
+        assert_eq!(monday.latest_tick(start, at(FRIDAY + 2, 23, 59)), None);
+        assert_eq!(monday.latest_tick(start, at(FRIDAY + 3, 8, 0)), Some(at(FRIDAY + 3, 8, 0)));
+        assert_eq!(monday.latest_tick(start, at(FRIDAY + 12, 12, 0)), Some(at(FRIDAY + 10, 8, 0)));
+        let friday = Recurrence::Weekly { weekday: 4, hour: 17, minute: 30 };
+        assert_eq!(friday.latest_tick(start, at(FRIDAY, 17, 30)), Some(at(FRIDAY, 17, 30)));
+        assert_eq!(friday.latest_tick(start, at(FRIDAY + 6, 23, 0)), Some(at(FRIDAY, 17, 30)));
+        // The epoch itself was a Thursday.
+        let thursday = Recurrence::Weekly { weekday: 3, hour: 0, minute: 0 };
+        assert_eq!(thursday.latest_tick(at(0, 0, 0), at(0, 12, 0)), Some(at(0, 0, 0)));
+    }
+}
@@ -0,0 +1,418 @@
+//! Templates for the questions and outcomes of spawned markets.
+//!
+//! A template is text with `{placeholder}` substitutions and conditional
+//! blocks, rendered from the parent's [`Resolution`]:
+//!
+//! ```text
+//! What will {winning_outcome} do after winning "{parent_question}"?
+//! {if winning_outcome == "Yes"}Will it pass{elif winning_outcome == "No"}Will it return{else}What next{end} by {expiry_date}?
+//! ```
+//!
+//! Placeholders: `parent_question`, `parent_market_id`, `outcome` and
+//! `winning_outcome`, `losing_outcomes` (comma separated), `total_stake`
+//! (whole tokens), `resolution_date` and `expiry_date` (`YYYY-MM-DD`).
+//! Conditions compare a placeholder with a quoted string using `==` or `!=`,
+//! or test that it is non-empty. Use `{{` and `}}` for literal braces outside
+//! of quoted strings.
+
+use linera_sdk::base::Timestamp;
+
+use crate::{day_index, whole_tokens, Resolution, SpawnHandlerError};
+
+/// Longest template accepted in a spawn rule.
+pub const MAX_TEMPLATE_LENGTH: usize = 1024;
+
+/// Deepest nesting of `{if}` blocks.
+const MAX_NESTING: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    ParentQuestion,
+    ParentMarketId,
+    WinningOutcome,
+    LosingOutcomes,
+    TotalStake,
+    ResolutionDate,
+    ExpiryDate,
+}
+
+impl Placeholder {
+    fn lookup(name: &str) -> Result<Self, SpawnHandlerError> {
+        let placeholder = match name {
+            "parent_question" => Placeholder::ParentQuestion,
+            "parent_market_id" => Placeholder::ParentMarketId,
+            "outcome" | "winning_outcome" => Placeholder::WinningOutcome,
+            "losing_outcomes" => Placeholder::LosingOutcomes,
+            "total_stake" => Placeholder::TotalStake,
+            "resolution_date" => Placeholder::ResolutionDate,
+            "expiry_date" => Placeholder::ExpiryDate,
+            _ => return Err(invalid(format!("unknown placeholder `{{{name}}}`"))),
+        };
+        Ok(placeholder)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    Equals(Placeholder, String),
+    NotEquals(Placeholder, String),
+    NonEmpty(Placeholder),
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Text(String),
+    Placeholder(Placeholder),
+    If {
+        branches: Vec<(Condition, Vec<Segment>)>,
+        otherwise: Vec<Segment>,
+    },
+}
+
+/// Data a template is rendered with.
+pub struct TemplateContext<'a> {
+    pub resolution: &'a Resolution,
+    pub expiry_time: Timestamp,
+}
+
+impl TemplateContext<'_> {
+    fn value(&self, placeholder: Placeholder) -> String {
+        let resolution = self.resolution;
+        match placeholder {
+            Placeholder::ParentQuestion => resolution.question.clone(),
+            Placeholder::ParentMarketId => resolution.market_id.clone(),
+            Placeholder::WinningOutcome => resolution.winning_outcome.clone(),
+            Placeholder::LosingOutcomes => resolution
+                .outcome_stakes
+                .iter()
+                .map(|(outcome, _)| outcome.as_str())
+                .filter(|outcome| *outcome != resolution.winning_outcome)
+                .collect::<Vec<_>>()
+                .join(", "),
+            Placeholder::TotalStake => whole_tokens(resolution.total_stake).to_string(),
+            Placeholder::ResolutionDate => format_date(resolution.resolved_at),
+            Placeholder::ExpiryDate => format_date(self.expiry_time),
+        }
+    }
+}
+
+/// A parsed template.
+#[derive(Debug, Clone)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+/// An `{if}` block being parsed.
+struct OpenIf {
+    branches: Vec<(Condition, Vec<Segment>)>,
+    otherwise: Option<Vec<Segment>>,
+}
+
+impl OpenIf {
+    fn current(&mut self) -> &mut Vec<Segment> {
+        match &mut self.otherwise {
+            Some(otherwise) => otherwise,
+            None => &mut self.branches.last_mut().expect("an `if` has a branch").1,
+        }
+    }
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, SpawnHandlerError> {
+        if source.len() > MAX_TEMPLATE_LENGTH {
+            return Err(invalid(format!(
+                "template is longer than {MAX_TEMPLATE_LENGTH} bytes"
+            )));
+        }
+        let mut root = Vec::new();
+        let mut open: Vec<OpenIf> = Vec::new();
+        let mut text = String::new();
+        let mut rest = source;
+        while let Some(index) = rest.find(['{', '}']) {
+            text.push_str(&rest[..index]);
+            let tail = &rest[index..];
+            if tail.starts_with("{{") || tail.starts_with("}}") {
+                text.push_str(&tail[..1]);
+                rest = &tail[2..];
+                continue;
+            }
+            if tail.starts_with('}') {
+                return Err(invalid("unmatched `}`, write `}}` for a literal brace"));
+            }
+            let end = tag_end(tail)
+                .ok_or_else(|| invalid("unterminated `{`, write `{{` for a literal brace"))?;
+            let tag = tail[1..end].trim();
+            rest = &tail[end + 1..];
+
+            let segments = open.last_mut().map_or(&mut root, OpenIf::current);
+            if !text.is_empty() {
+                segments.push(Segment::Text(std::mem::take(&mut text)));
+            }
+            let (keyword, argument) = tag.split_once(' ').unwrap_or((tag, ""));
+            match keyword {
+                "if" => {
+                    if open.len() == MAX_NESTING {
+                        return Err(invalid(format!(
+                            "`{{if}}` blocks are nested more than {MAX_NESTING} levels deep"
+                        )));
+                    }
+                    open.push(OpenIf {
+                        branches: vec![(parse_condition(argument)?, Vec::new())],
+                        otherwise: None,
+                    });
+                }
+                "elif" => {
+                    let block = open
+                        .last_mut()
+                        .filter(|block| block.otherwise.is_none())
+                        .ok_or_else(|| invalid("`{elif}` outside of an `{if}` block"))?;
+                    block.branches.push((parse_condition(argument)?, Vec::new()));
+                }
+                "else" if argument.is_empty() => {
+                    let block = open
+                        .last_mut()
+                        .filter(|block| block.otherwise.is_none())
+                        .ok_or_else(|| invalid("`{else}` outside of an `{if}` block"))?;
+                    block.otherwise = Some(Vec::new());
+                }
+                "end" if argument.is_empty() => {
+                    let block = open
+                        .pop()
+                        .ok_or_else(|| invalid("`{end}` without a matching `{if}`"))?;
+                    let segment = Segment::If {
+                        branches: block.branches,
+                        otherwise: block.otherwise.unwrap_or_default(),
+                    };
+                    open.last_mut().map_or(&mut root, OpenIf::current).push(segment);
+                }
+                _ => segments.push(Segment::Placeholder(Placeholder::lookup(tag)?)),
+            }
+        }
+        if !open.is_empty() {
+            return Err(invalid("`{if}` block is missing its `{end}`"));
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            root.push(Segment::Text(text));
+        }
+        Ok(Template { segments: root })
+    }
+
+    pub fn render(&self, context: &TemplateContext) -> String {
+        let mut output = String::new();
+        render_segments(&self.segments, context, &mut output);
+        output
+    }
+}
+
+/// Index of the `}` closing the tag `tail` starts with, skipping any inside
+/// quoted literals.
+fn tag_end(tail: &str) -> Option<usize> {
+    let mut quoted = false;
+    for (index, character) in tail.char_indices() {
+        match character {
+            '"' => quoted = !quoted,
+            '}' if !quoted => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn invalid(message: impl Into<String>) -> SpawnHandlerError {
+    SpawnHandlerError::InvalidTemplate(message.into())
+}
+
+fn parse_condition(source: &str) -> Result<Condition, SpawnHandlerError> {
+    let comparison = source
+        .split_once("==")
+        .map(|(left, right)| (left, right, true))
+        .or_else(|| source.split_once("!=").map(|(left, right)| (left, right, false)));
+    let Some((left, right, equals)) = comparison else {
+        return Ok(Condition::NonEmpty(Placeholder::lookup(source.trim())?));
+    };
+    let placeholder = Placeholder::lookup(left.trim())?;
+    let literal = right
+        .trim()
+        .strip_prefix('"')
+        .and_then(|literal| literal.strip_suffix('"'))
+        .ok_or_else(|| invalid(format!("expected a quoted string in `{source}`")))?
+        .to_string();
+    Ok(if equals {
+        Condition::Equals(placeholder, literal)
+    } else {
+        Condition::NotEquals(placeholder, literal)
+    })
+}
+
+fn render_segments(segments: &[Segment], context: &TemplateContext, output: &mut String) {
+    for segment in segments {
+        match segment {
+            Segment::Text(text) => output.push_str(text),
+            Segment::Placeholder(placeholder) => output.push_str(&context.value(*placeholder)),
+            Segment::If {
+                branches,
+                otherwise,
+            } => {
+                let chosen = branches
+                    .iter()
+                    .find(|(condition, _)| holds(condition, context))
+                    .map_or(otherwise, |(_, body)| body);
+                render_segments(chosen, context, output);
+            }
+        }
+    }
+}
+
+fn holds(condition: &Condition, context: &TemplateContext) -> bool {
+    match condition {
+        Condition::Equals(placeholder, literal) => context.value(*placeholder) == *literal,
+        Condition::NotEquals(placeholder, literal) => context.value(*placeholder) != *literal,
+        Condition::NonEmpty(placeholder) => !context.value(*placeholder).is_empty(),
+    }
+}
+
+/// Formats the UTC calendar date of `timestamp` as `YYYY-MM-DD`.
+pub fn format_date(timestamp: Timestamp) -> String {
+    // Days-to-civil conversion from Howard Hinnant's date algorithms.
+    let days = day_index(timestamp) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linera_sdk::base::Amount;
+
+    const DAY: u64 = 86_400_000_000;
+
+    /// Date `days` after the epoch, `micros` into the day.
+    fn date(days: u64, micros: u64) -> String {
+        format_date(Timestamp::from(days * DAY + micros))
+    }
+
+    fn resolution(winning_outcome: &str) -> Resolution {
+        Resolution {
+            market_id: "m1".to_string(),
+            question: "Who wins?".to_string(),
+            winning_outcome: winning_outcome.to_string(),
+            total_stake: Amount::from_tokens(120),
+            outcome_stakes: vec![
+                ("Yes".to_string(), Amount::from_tokens(70)),
+                ("No".to_string(), Amount::from_tokens(40)),
+                ("Maybe".to_string(), Amount::from_tokens(10)),
+            ],
+            depth: 0,
+            ancestors: Vec::new(),
+            resolved_at: Timestamp::from(19_782 * DAY),
+            creators: Vec::new(),
+        }
+    }
+
+    fn render(source: &str, winning_outcome: &str) -> String {
+        let resolution = resolution(winning_outcome);
+        let context = TemplateContext {
+            resolution: &resolution,
+            expiry_time: Timestamp::from(19_783 * DAY),
+        };
+        Template::parse(source).unwrap().render(&context)
+    }
+
+    fn parse_error(source: &str) -> String {
+        match Template::parse(source) {
+            Err(SpawnHandlerError::InvalidTemplate(message)) => message,
+            other => panic!("expected an invalid template, got {other:?}"),
+        }
+    }
+
+    fn nested(levels: usize) -> String {
+        format!("{}x{}", "{if outcome}".repeat(levels), "{end}".repeat(levels))
+    }
+
+    #[test]
+    fn renders_placeholders() {
+        let source = "After {outcome} won \"{parent_question}\" ({parent_market_id}, \
+            {total_stake} staked on {resolution_date}), by {expiry_date}?";
+        assert_eq!(
+            render(source, "Yes"),
+            "After Yes won \"Who wins?\" (m1, 120 staked on 2024-02-29), by 2024-03-01?"
+        );
+        assert_eq!(render("{winning_outcome}", "No"), "No");
+    }
+
+    #[test]
+    fn lists_losing_outcomes_in_order() {
+        assert_eq!(render("{losing_outcomes}", "Yes"), "No, Maybe");
+        assert_eq!(render("{losing_outcomes}", "No"), "Yes, Maybe");
+    }
+
+    #[test]
+    fn selects_the_first_branch_that_holds() {
+        let source = r#"{if outcome == "Yes"}pass{elif outcome != "No"}other{else}fail{end}"#;
+        assert_eq!(render(source, "Yes"), "pass");
+        assert_eq!(render(source, "Maybe"), "other");
+        assert_eq!(render(source, "No"), "fail");
+        assert_eq!(render(r#"{if outcome == "No"}fail{end}!"#, "Yes"), "!");
+        assert_eq!(render("{if losing_outcomes}lost{end}", "Yes"), "lost");
+    }
+
+    #[test]
+    fn limits_nesting() {
+        assert_eq!(render(&nested(MAX_NESTING), "Yes"), "x");
+        assert!(parse_error(&nested(MAX_NESTING + 1)).contains("nested more than 8 levels"));
+    }
+
+    #[test]
+    fn escapes_braces() {
+        assert_eq!(render("{{outcome}} is {outcome}", "Yes"), "{outcome} is Yes");
+        assert_eq!(render("}}{{", "Yes"), "}{");
+        assert_eq!(render(r#"{if outcome == "a}b"}matched{end}"#, "a}b"), "matched");
+    }
+
+    #[test]
+    fn rejects_unknown_placeholders() {
+        assert_eq!(parse_error("{winner}"), "unknown placeholder `{winner}`");
+        assert_eq!(parse_error(r#"{if winner == "Yes"}x{end}"#), "unknown placeholder `{winner}`");
+    }
+
+    #[test]
+    fn rejects_unbalanced_blocks() {
+        assert!(parse_error("{if outcome}x").contains("missing its `{end}`"));
+        assert!(parse_error("x{end}").contains("without a matching `{if}`"));
+        assert!(parse_error("{else}").contains("outside of an `{if}` block"));
+        assert!(parse_error("{if outcome}x{else}y{elif outcome}z{end}").contains("`{elif}` outside"));
+        assert!(parse_error("{outcome").contains("unterminated `{`"));
+        assert!(parse_error("outcome}").contains("unmatched `}`"));
+    }
+
+    #[test]
+    fn formats_dates_around_leap_days() {
+        assert_eq!(date(0, 0), "1970-01-01");
+        // 2000 is a leap year, as a multiple of 400.
+        assert_eq!(date(11_015, 0), "2000-02-28");
+        assert_eq!(date(11_016, 0), "2000-02-29");
+        assert_eq!(date(11_017, 0), "2000-03-01");
+        // 2023 is not a leap year, so March follows February 28.
+        assert_eq!(date(19_416, DAY - 1), "2023-02-28");
+        assert_eq!(date(19_417, 0), "2023-03-01");
+        assert_eq!(date(19_782, 0), "2024-02-29");
+        assert_eq!(date(19_783, 0), "2024-03-01");
+        assert_eq!(date(20_088, 0), "2024-12-31");
+        // 2100 is not a leap year, as a multiple of 100 but not of 400.
+        assert_eq!(date(47_540, 0), "2100-02-28");
+        assert_eq!(date(47_541, 0), "2100-03-01");
+    }
+}
@@ -0,0 +1,739 @@
+use linera_sdk::{
+    abi::{ContractAbi, ServiceAbi},
+    service::{service, ServiceRuntime},
+    views::{RegisterView, View},
+    Service, WithServiceAbi,
+    base::{Account, AccountOwner, Amount, ChainId, Timestamp},
+};
+use async_graphql::{EmptySubscription, InputObject, Object, Request, Response, Schema, SimpleObject};
+use std::{str::FromStr, sync::Arc};
+use serde::{Deserialize, Deserializer};
+
+// Share ABI and state types with the contract
+pub use spawn_handler_abi::spawn_handler;
+use fractal_abi::{
+    admin::AdminSet,
+    factory::{RoyaltyShare, TreeLimits},
+};
+use spawn_handler_abi::{
+    evaluation::{Evaluator, RuleMatch},
+    pattern::PatternCache,
+    rule_pack::RulePack,
+    day_index, DeadLetter, OutcomeTemplate, PendingSpawn, Resolution, RuleChange, RuleCreationPolicy,
+    RuleScope, RuleSettings, SpawnHandlerError, SpawnHandlerStateData, SpawnLimits, SpawnRule,
+    SpawnStatus, SpawnTemplate, TriggerCondition,
+};
+
+/// Same root view as the contract, so the service reads the stored state as-is.
+type SpawnHandlerState = RegisterView<SpawnHandlerStateData>;
+
+pub struct SpawnHandlerService {
+    state: SpawnHandlerState,
+    runtime: Arc<ServiceRuntime<Self>>,
+}
+
+/// The contract's ABI plus GraphQL queries, kept here so the shared ABI crate
+/// does not pull async-graphql into the contracts linking it.
+pub struct SpawnHandlerServiceAbi;
+
+impl ContractAbi for SpawnHandlerServiceAbi {
+    type Operation = spawn_handler::Operation;
+    type Response = spawn_handler::ResponseBytes;
+}
+
+impl ServiceAbi for SpawnHandlerServiceAbi {
+    type Query = Request;
+    type QueryResponse = Response;
+}
+
+impl WithServiceAbi for SpawnHandlerService {
+    type Abi = SpawnHandlerServiceAbi;
+}
+
+impl SpawnHandlerService {
+    fn data(&self) -> SpawnHandlerStateData {
+        self.state.get().clone()
+    }
+}
+
+/// A spawn rule, with its full definition as JSON.
+#[derive(SimpleObject)]
+struct RuleView {
+    rule_id: String,
+    active: bool,
+    created_by: String,
+    priority: i32,
+    exclusive: bool,
+    scope_market_id: Option<String>,
+    version: u32,
+    definition: String,
+}
+
+impl From<&SpawnRule> for RuleView {
+    fn from(rule: &SpawnRule) -> Self {
+        RuleView {
+            rule_id: rule.rule_id.clone(),
+            active: rule.active,
+            created_by: format!("{}", rule.created_by),
+            priority: rule.settings.priority,
+            exclusive: rule.settings.exclusive,
+            scope_market_id: rule.scope.as_ref().map(|scope| scope.market_id.clone()),
+            version: rule.version,
+            definition: serde_json::to_string(rule).unwrap_or_default(),
+        }
+    }
+}
+
+/// An entry of the rule history, with the rule as it was after the change as JSON.
+#[derive(SimpleObject)]
+struct RuleChangeView {
+    rule_id: String,
+    version: u32,
+    change: String,
+    changed_by: String,
+    changed_at: u64, // Microseconds since the epoch
+    rule: String,
+}
+
+impl From<&RuleChange> for RuleChangeView {
+    fn from(change: &RuleChange) -> Self {
+        RuleChangeView {
+            rule_id: change.rule_id.clone(),
+            version: change.version,
+            change: format!("{:?}", change.change),
+            changed_by: format!("{}", change.changed_by),
+            changed_at: change.changed_at.micros(),
+            rule: serde_json::to_string(&change.rule).unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct SpawnView {
+    spawn_id: String,
+    rule_id: String,
+    parent_market_id: Option<String>,
+    parent_outcome: String,
+    question: String,
+    outcomes: Vec<String>,
+    seed_liquidity: String,
+    royalties: Vec<RoyaltyShareView>,
+    scheduled_time: u64, // Microseconds since the epoch
+    status: String,
+    attempts: u32,
+    retry_at: Option<u64>,
+    market_id: Option<String>,
+    review: Option<String>, // The approval decision as JSON
+}
+
+impl From<&PendingSpawn> for SpawnView {
+    fn from(spawn: &PendingSpawn) -> Self {
+        SpawnView {
+            spawn_id: spawn.spawn_id.clone(),
+            rule_id: spawn.rule_id.clone(),
+            parent_market_id: spawn.parent_market_id.clone(),
+            parent_outcome: spawn.parent_outcome.clone(),
+            question: spawn.question.clone(),
+            outcomes: spawn.outcomes.clone(),
+            seed_liquidity: format!("{}", spawn.seed_liquidity),
+            royalties: spawn.royalties.iter().map(RoyaltyShareView::from).collect(),
+            scheduled_time: spawn.scheduled_time.micros(),
+            status: format!("{:?}", spawn.status),
+            attempts: spawn.attempts,
+            retry_at: spawn.retry_at.map(|retry_at| retry_at.micros()),
+            market_id: spawn.market_id.clone(),
+            review: spawn
+                .review
+                .as_ref()
+                .map(|review| serde_json::to_string(review).unwrap_or_default()),
+        }
+    }
+}
+
+/// A share of the spawned market's fees; the parent creator's recipient is
+/// only known once the factory creates the market.
+#[derive(SimpleObject)]
+struct RoyaltyShareView {
+    role: String,
+    recipient: Option<String>,
+    basis_points: u32,
+}
+
+impl From<&RoyaltyShare> for RoyaltyShareView {
+    fn from(share: &RoyaltyShare) -> Self {
+        RoyaltyShareView {
+            role: format!("{:?}", share.role),
+            recipient: share.recipient.map(|c| format!("{}", c)),
+            basis_points: share.basis_points,
+        }
+    }
+}
+
+#[derive(SimpleObject)]
+struct DeadLetterView {
+    spawn_id: String,
+    reason: String,
+    failed_at: u64, // Microseconds since the epoch
+}
+
+impl From<&DeadLetter> for DeadLetterView {
+    fn from(letter: &DeadLetter) -> Self {
+        DeadLetterView {
+            spawn_id: letter.spawn_id.clone(),
+            reason: letter.reason.clone(),
+            failed_at: letter.failed_at.micros(),
+        }
+    }
+}
+
+/// A rule that matched a simulated resolution, and the child it would spawn.
+#[derive(SimpleObject)]
+struct SimulatedSpawn {
+    rule_id: String,
+    priority: i32,
+    exclusive: bool,
+    /// False when the rule skips the winning outcome or its template fails to render.
+    rendered: bool,
+    /// Whether the global and per-rule caps would let the spawn be queued now.
+    within_limits: bool,
+    question: Option<String>,
+    outcomes: Vec<String>,
+    scheduled_time: Option<u64>, // Microseconds since the epoch
+    expiry_time: Option<u64>,
+    seed_liquidity: Option<String>,
+}
+
+/// Text format of an imported or exported rule pack. TOML is handled only
+/// here, so the contract does not carry a TOML parser.
+#[derive(Clone, Copy)]
+enum RulePackFormat {
+    Json,
+    Toml,
+}
+
+fn invalid_pack(error: impl std::fmt::Display) -> SpawnHandlerError {
+    SpawnHandlerError::InvalidRulePack(error.to_string())
+}
+
+impl FromStr for RulePackFormat {
+    type Err = SpawnHandlerError;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format.to_ascii_lowercase().as_str() {
+            "json" => Ok(RulePackFormat::Json),
+            "toml" => Ok(RulePackFormat::Toml),
+            _ => Err(invalid_pack(format_args!("unknown format {format}"))),
+        }
+    }
+}
+
+impl RulePackFormat {
+    fn parse(self, source: &str) -> Result<RulePack, SpawnHandlerError> {
+        match self {
+            RulePackFormat::Json => serde_json::from_str(source).map_err(invalid_pack),
+            RulePackFormat::Toml => toml::from_str(source).map_err(invalid_pack),
+        }
+    }
+
+    fn render(self, pack: &RulePack) -> Result<String, SpawnHandlerError> {
+        match self {
+            RulePackFormat::Json => serde_json::to_string_pretty(pack).map_err(invalid_pack),
+            RulePackFormat::Toml => toml::to_string_pretty(pack).map_err(invalid_pack),
+        }
+    }
+}
+
+/// Tokens staked on one outcome of a simulated resolution.
+#[derive(InputObject)]
+struct OutcomeStake {
+    outcome: String,
+    stake_tokens: u64,
+}
+
+/// Creator of the simulated market or one of its ancestors.
+#[derive(InputObject)]
+struct MarketCreator {
+    market_id: String,
+    creator: String,
+}
+
+/// Body of an `editSpawnRule` mutation; omitted parts are kept.
+#[derive(Deserialize)]
+struct RuleEdit {
+    #[serde(default)]
+    trigger_condition: Option<TriggerCondition>,
+    #[serde(default)]
+    spawn_template: Option<SpawnTemplate>,
+    #[serde(default)]
+    outcome_templates: Option<Vec<OutcomeTemplate>>,
+    #[serde(default)]
+    skip_unmatched_outcomes: Option<bool>,
+    #[serde(default)]
+    settings: Option<RuleSettings>,
+    #[serde(default, deserialize_with = "present")] // `null` removes the scope
+    scope: Option<Option<RuleScope>>,
+}
+
+/// Parses a token amount, such as `2.5`.
+fn parse_amount(amount: &str) -> Result<Amount, String> {
+    Amount::from_str(amount).map_err(|_| format!("invalid amount: {amount}"))
+}
+
+/// Parses the owners of an admin set.
+fn admin_set(owners: &[String], threshold: u32) -> Result<AdminSet, String> {
+    let owners = owners
+        .iter()
+        .map(|owner| owner.parse::<AccountOwner>().map_err(|_| format!("invalid account owner: {owner}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(AdminSet { owners, threshold })
+}
+
+/// Deserializes a field that is present, possibly as `null`, into `Some`.
+fn present<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Body of a `createSpawnRule` mutation.
+#[derive(Deserialize)]
+struct RuleDefinition {
+    trigger_condition: TriggerCondition,
+    spawn_template: SpawnTemplate,
+    #[serde(default)]
+    outcome_templates: Vec<OutcomeTemplate>,
+    #[serde(default)]
+    skip_unmatched_outcomes: bool,
+    #[serde(default)]
+    settings: RuleSettings,
+    #[serde(default)]
+    scope: Option<RuleScope>,
+}
+
+impl Service for SpawnHandlerService {
+    type Parameters = spawn_handler::Parameters;
+
+    async fn new(runtime: ServiceRuntime<Self>) -> Self {
+        let state = <SpawnHandlerState as View>::load(runtime.root_view_storage_context())
+            .await
+            .expect("Failed to load state");
+        SpawnHandlerService {
+            state,
+            runtime: Arc::new(runtime),
+        }
+    }
+
+    async fn handle_query(&self, request: Request) -> Response {
+        // GraphQL schema
+        struct QueryRoot {
+            data: SpawnHandlerStateData,
+            parameters: spawn_handler::Parameters,
+            now: Timestamp,
+        }
+
+        impl QueryRoot {
+            fn spawns(&self, statuses: &[SpawnStatus]) -> Vec<SpawnView> {
+                self.data
+                    .pending_spawns
+                    .iter()
+                    .filter(|spawn| statuses.contains(&spawn.status))
+                    .map(SpawnView::from)
+                    .collect()
+            }
+        }
+
+        #[Object]
+        impl QueryRoot {
+            /// Rules sorted by id, optionally only those created by `created_by` or
+            /// with the given `active` flag.
+            async fn rules(&self, created_by: Option<String>, active: Option<bool>) -> Vec<RuleView> {
+                let mut rules = self
+                    .data
+                    .spawn_rules
+                    .values()
+                    .filter(|rule| {
+                        created_by
+                            .as_ref()
+                            .is_none_or(|creator| *creator == format!("{}", rule.created_by))
+                    })
+                    .filter(|rule| active.is_none_or(|active| rule.active == active))
+                    .map(RuleView::from)
+                    .collect::<Vec<_>>();
+                rules.sort_by(|a, b| a.rule_id.cmp(&b.rule_id));
+                rules
+            }
+            async fn rule(&self, rule_id: String) -> Option<RuleView> {
+                self.data.spawn_rules.get(&rule_id).map(RuleView::from)
+            }
+            /// Every change to rules, oldest first, optionally for one rule only.
+            async fn rule_history(&self, rule_id: Option<String>) -> Vec<RuleChangeView> {
+                self.data
+                    .rule_history
+                    .iter()
+                    .filter(|change| rule_id.as_ref().is_none_or(|id| *id == change.rule_id))
+                    .map(RuleChangeView::from)
+                    .collect()
+            }
+            /// The given rules, or all of them, as a rule pack in `format` (`json`
+            /// by default, or `toml`) ready to be kept in a repository.
+            async fn export_rules(
+                &self,
+                rule_ids: Option<Vec<String>>,
+                format: Option<String>,
+            ) -> async_graphql::Result<String> {
+                let format = format.as_deref().unwrap_or("json").parse::<RulePackFormat>()?;
+                let pack = match rule_ids {
+                    None => RulePack::from_rules(self.data.spawn_rules.values()),
+                    Some(rule_ids) => RulePack::from_rules(
+                        rule_ids
+                            .iter()
+                            .map(|rule_id| {
+                                self.data.spawn_rules.get(rule_id).ok_or(SpawnHandlerError::RuleNotFound)
+                            })
+                            .collect::<Result<Vec<_>, _>>()?,
+                    ),
+                };
+                Ok(format.render(&pack)?)
+            }
+            /// Spawns whose market has not been confirmed yet.
+            async fn pending_spawns(&self) -> Vec<SpawnView> {
+                self.spawns(&[SpawnStatus::Queued, SpawnStatus::Dispatched])
+            }
+            async fn processed_spawns(&self) -> Vec<SpawnView> { self.spawns(&[SpawnStatus::Confirmed]) }
+            async fn failed_spawns(&self) -> Vec<SpawnView> { self.spawns(&[SpawnStatus::Failed]) }
+            async fn cancelled_spawns(&self) -> Vec<SpawnView> { self.spawns(&[SpawnStatus::Cancelled]) }
+            /// Spawns held by rules that require approval, awaiting `approveSpawn` or `rejectSpawn`.
+            async fn awaiting_approval(&self) -> Vec<SpawnView> { self.spawns(&[SpawnStatus::AwaitingApproval]) }
+            async fn rejected_spawns(&self) -> Vec<SpawnView> { self.spawns(&[SpawnStatus::Rejected]) }
+            /// Spawns out of dispatch attempts, awaiting `requeueSpawn` or `cancelSpawn`.
+            async fn dead_letters(&self) -> Vec<DeadLetterView> {
+                self.data.dead_letters.iter().map(DeadLetterView::from).collect()
+            }
+            /// Bond posted for every new rule by anyone but the admin.
+            async fn creation_bond(&self) -> String { format!("{}", self.data.creation_policy.bond) }
+            async fn max_rules_per_creator(&self) -> Option<u32> { self.data.creation_policy.max_rules_per_creator }
+            async fn allow_list_only(&self) -> bool { self.data.creation_policy.allow_list_only }
+            async fn allowed_creators(&self) -> Vec<String> {
+                self.data.allowed_creators.iter().map(|c| format!("{}", c)).collect()
+            }
+            async fn rule_bond(&self, rule_id: String) -> Option<String> {
+                self.data.rule_bonds.get(&rule_id).map(|bond| format!("{}", bond.amount))
+            }
+            async fn spawn_count(&self) -> u64 { self.data.spawn_count }
+            async fn liquidity_reserve(&self) -> String { format!("{}", self.data.liquidity_reserve) }
+            async fn admins(&self) -> Vec<String> {
+                let admins = self.data.admin.current.iter().flat_map(|set| &set.owners);
+                admins.map(|c| format!("{}", c)).collect()
+            }
+            async fn admin_threshold(&self) -> Option<u32> { self.data.admin.current.as_ref().map(|set| set.threshold) }
+            /// Owners of a proposed admin handover, not yet accepted.
+            async fn proposed_admins(&self) -> Vec<String> {
+                let proposed = self.data.admin.pending.iter().flat_map(|t| &t.proposed.owners);
+                proposed.map(|c| format!("{}", c)).collect()
+            }
+            async fn admin_transfer_approvals(&self) -> Vec<String> {
+                let approvals = self.data.admin.pending.iter().flat_map(|t| &t.approvals);
+                approvals.map(|c| format!("{}", c)).collect()
+            }
+            async fn factory_id(&self) -> String { format!("{}", self.parameters.factory_id) }
+            async fn factory_chain_id(&self) -> String { format!("{}", self.parameters.factory_chain_id) }
+            async fn home_chain_id(&self) -> String { format!("{}", self.parameters.home_chain_id) }
+            async fn auto_process(&self) -> bool { self.parameters.auto_process }
+            /// Dry run: evaluates every active rule against a hypothetical resolution
+            /// happening now, in the order the contract would, without changing state.
+            /// `outcome_stakes` lists the tokens staked on each outcome. Give
+            /// `market_id`, `ancestors` (nearest parent first) and the `creators`
+            /// of those markets to exercise scoped and subtree rules.
+            async fn simulate_resolution(
+                &self,
+                question: String,
+                winning_outcome: String,
+                outcome_stakes: Vec<OutcomeStake>,
+                market_id: Option<String>,
+                ancestors: Option<Vec<String>>,
+                creators: Option<Vec<MarketCreator>>,
+            ) -> async_graphql::Result<Vec<SimulatedSpawn>> {
+                let creators = creators
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|MarketCreator { market_id, creator }| {
+                        let owner = creator
+                            .parse::<AccountOwner>()
+                            .map_err(|_| format!("invalid account owner: {creator}"))?;
+                        Ok((market_id, owner))
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                let outcome_stakes = outcome_stakes
+                    .into_iter()
+                    .map(|stake| (stake.outcome, Amount::from_tokens(u128::from(stake.stake_tokens))))
+                    .collect::<Vec<_>>();
+                let total_stake = outcome_stakes
+                    .iter()
+                    .fold(Amount::ZERO, |total, (_, stake)| total.saturating_add(*stake));
+                let ancestors = ancestors.unwrap_or_default();
+                let resolution = Resolution {
+                    market_id: market_id.unwrap_or_else(|| "simulated".to_string()),
+                    question,
+                    outcome_stakes,
+                    winning_outcome,
+                    total_stake,
+                    depth: u32::try_from(ancestors.len()).unwrap_or(u32::MAX),
+                    ancestors,
+                    resolved_at: self.now,
+                    creators,
+                };
+                let mut patterns = PatternCache::default();
+                let mut evaluator = Evaluator {
+                    patterns: &mut patterns,
+                    now: self.now,
+                    admin: &self.data.admin,
+                };
+                let mut counters = self.data.counters.clone();
+                counters.roll_over(day_index(self.now));
+                let matches = evaluator.matching_rules(&self.data.spawn_rules, &resolution);
+                Ok(matches
+                    .into_iter()
+                    .map(|RuleMatch { rule, spawn }| {
+                        let within_limits = counters.allows(&self.data.limits, &rule, &resolution);
+                        let seed_allowance = counters.seed_allowance(&self.data.limits, &resolution.market_id);
+                        let seed_liquidity = spawn.as_ref().map(|spawn| spawn.seed_liquidity.min(seed_allowance));
+                        if let (true, Some(seed_liquidity)) = (within_limits, seed_liquidity) {
+                            counters.record(&rule.rule_id, &resolution.market_id, seed_liquidity);
+                        }
+                        SimulatedSpawn {
+                            rule_id: rule.rule_id,
+                            priority: rule.settings.priority,
+                            exclusive: rule.settings.exclusive,
+                            rendered: spawn.is_some(),
+                            within_limits,
+                            question: spawn.as_ref().map(|spawn| spawn.question.clone()),
+                            outcomes: spawn.as_ref().map(|spawn| spawn.outcomes.clone()).unwrap_or_default(),
+                            scheduled_time: spawn.as_ref().map(|spawn| spawn.scheduled_time.micros()),
+                            expiry_time: spawn.as_ref().map(|spawn| spawn.expiry_time.micros()),
+                            seed_liquidity: seed_liquidity.map(|seed| format!("{}", seed)),
+                        }
+                    })
+                    .collect())
+            }
+        }
+
+        struct MutationRoot {
+            runtime: Arc<ServiceRuntime<SpawnHandlerService>>,
+        }
+
+        #[Object]
+        impl MutationRoot {
+            /// Creates a rule from a JSON `definition` with the fields of the
+            /// contract's `CreateSpawnRule` operation other than `rule_id`.
+            async fn create_spawn_rule(&self, rule_id: String, definition: String) -> async_graphql::Result<bool> {
+                let definition: RuleDefinition = serde_json::from_str(&definition)
+                    .map_err(|error| format!("invalid rule definition: {error}"))?;
+                self.runtime.schedule_operation(&spawn_handler::Operation::CreateSpawnRule {
+                    rule_id,
+                    trigger_condition: definition.trigger_condition,
+                    spawn_template: definition.spawn_template,
+                    outcome_templates: definition.outcome_templates,
+                    skip_unmatched_outcomes: definition.skip_unmatched_outcomes,
+                    settings: definition.settings,
+                    scope: definition.scope,
+                });
+                Ok(true)
+            }
+            /// Imports a rule pack written in `format` (`json` by default, or `toml`).
+            async fn import_rules(&self, pack: String, format: Option<String>) -> async_graphql::Result<bool> {
+                let format = format.as_deref().unwrap_or("json").parse::<RulePackFormat>()?;
+                let pack = format.parse(&pack)?;
+                pack.validate()?;
+                self.runtime.schedule_operation(&spawn_handler::Operation::ImportRules { pack });
+                Ok(true)
+            }
+            async fn update_spawn_rule(&self, rule_id: String, active: bool) -> bool {
+                self.runtime.schedule_operation(&spawn_handler::Operation::UpdateSpawnRule {
+                    rule_id,
+                    active,
+                });
+                true
+            }
+            /// Edits a rule from a JSON `changes` object with any of `trigger_condition`,
+            /// `spawn_template`, `outcome_templates`, `skip_unmatched_outcomes`,
+            /// `settings` and `scope`.
+            async fn edit_spawn_rule(&self, rule_id: String, changes: String) -> async_graphql::Result<bool> {
+                let changes: RuleEdit = serde_json::from_str(&changes)
+                    .map_err(|error| format!("invalid rule changes: {error}"))?;
+                self.runtime.schedule_operation(&spawn_handler::Operation::EditSpawnRule {
+                    rule_id,
+                    trigger_condition: changes.trigger_condition,
+                    spawn_template: changes.spawn_template,
+                    outcome_templates: changes.outcome_templates,
+                    skip_unmatched_outcomes: changes.skip_unmatched_outcomes,
+                    settings: changes.settings,
+                    scope: changes.scope,
+                });
+                Ok(true)
+            }
+            async fn delete_spawn_rule(&self, rule_id: String) -> bool {
+                self.runtime.schedule_operation(&spawn_handler::Operation::DeleteSpawnRule { rule_id });
+                true
+            }
+            async fn transfer_rule_ownership(&self, rule_id: String, new_owner: String) -> async_graphql::Result<bool> {
+                let new_owner: AccountOwner = new_owner
+                    .parse()
+                    .map_err(|_| format!("invalid account owner: {new_owner}"))?;
+                self.runtime.schedule_operation(&spawn_handler::Operation::TransferRuleOwnership {
+                    rule_id,
+                    new_owner,
+                });
+                Ok(true)
+            }
+            async fn requeue_spawn(&self, spawn_id: String) -> bool {
+                self.runtime.schedule_operation(&spawn_handler::Operation::RequeueSpawn { spawn_id });
+                true
+            }
+            async fn cancel_spawn(&self, spawn_id: String) -> bool {
+                self.runtime.schedule_operation(&spawn_handler::Operation::CancelSpawn { spawn_id });
+                true
+            }
+            /// Releases a spawn awaiting approval, replacing its question or outcomes when given.
+            async fn approve_spawn(
+                &self,
+                spawn_id: String,
+                question: Option<String>,
+                outcomes: Option<Vec<String>>,
+                note: Option<String>,
+            ) -> bool {
+                self.runtime.schedule_operation(&spawn_handler::Operation::ApproveSpawn {
+                    spawn_id,
+                    question,
+                    outcomes,
+                    note,
+                });
+                true
+            }
+            async fn reject_spawn(&self, spawn_id: String, note: Option<String>) -> bool {
+                self.runtime.schedule_operation(&spawn_handler::Operation::RejectSpawn { spawn_id, note });
+                true
+            }
+            async fn slash_rule_bond(&self, rule_id: String) -> bool {
+                self.runtime.schedule_operation(&spawn_handler::Operation::SlashRuleBond { rule_id });
+                true
+            }
+            async fn set_creator_allowed(&self, creator: String, allowed: bool) -> async_graphql::Result<bool> {
+                let creator: AccountOwner = creator
+                    .parse()
+                    .map_err(|_| format!("invalid account owner: {creator}"))?;
+                self.runtime.schedule_operation(&spawn_handler::Operation::SetCreatorAllowed {
+                    creator,
+                    allowed,
+                });
+                Ok(true)
+            }
+            /// Gives the admin role to `owners`, `threshold` of whom are needed for a
+            /// handover; only the signer who instantiated the handler may.
+            async fn initialize(&self, owners: Vec<String>, threshold: u32) -> async_graphql::Result<bool> {
+                let admin = admin_set(&owners, threshold)?;
+                self.runtime.schedule_operation(&spawn_handler::Operation::Initialize { admin });
+                Ok(true)
+            }
+            /// Proposes handing the admin role to `owners`, `threshold` of whom
+            /// will be needed for the next handover.
+            async fn transfer_admin(&self, owners: Vec<String>, threshold: u32) -> async_graphql::Result<bool> {
+                let new_admin = admin_set(&owners, threshold)?;
+                self.runtime.schedule_operation(&spawn_handler::Operation::TransferAdmin { new_admin });
+                Ok(true)
+            }
+            async fn accept_admin(&self) -> bool {
+                self.runtime.schedule_operation(&spawn_handler::Operation::AcceptAdmin);
+                true
+            }
+            async fn cancel_admin_transfer(&self) -> bool {
+                self.runtime.schedule_operation(&spawn_handler::Operation::CancelAdminTransfer);
+                true
+            }
+            /// Replaces the `bond` posted for new rules, in tokens, the rules each
+            /// creator may own and whether only allow-listed signers create rules.
+            async fn set_creation_policy(
+                &self,
+                bond: String,
+                max_rules_per_creator: Option<u32>,
+                allow_list_only: bool,
+            ) -> async_graphql::Result<bool> {
+                let policy = RuleCreationPolicy {
+                    bond: parse_amount(&bond)?,
+                    max_rules_per_creator,
+                    allow_list_only,
+                };
+                self.runtime.schedule_operation(&spawn_handler::Operation::SetCreationPolicy { policy });
+                Ok(true)
+            }
+            /// Replaces the caps applied across all rules; `max_seed_per_resolution`
+            /// is in tokens.
+            async fn set_spawn_limits(
+                &self,
+                max_spawns_per_resolution: Option<u32>,
+                max_spawns_per_day: Option<u32>,
+                max_depth: u32,
+                max_children: u32,
+                max_seed_per_resolution: String,
+            ) -> async_graphql::Result<bool> {
+                let limits = SpawnLimits {
+                    max_spawns_per_resolution,
+                    max_spawns_per_day,
+                    tree: TreeLimits {
+                        max_depth,
+                        max_children,
+                    },
+                    max_seed_per_resolution: parse_amount(&max_seed_per_resolution)?,
+                };
+                self.runtime.schedule_operation(&spawn_handler::Operation::SetSpawnLimits { limits });
+                Ok(true)
+            }
+            /// Moves `amount` tokens from the signer into the liquidity reserve.
+            async fn deposit_reserve(&self, amount: String) -> async_graphql::Result<bool> {
+                let amount = parse_amount(&amount)?;
+                self.runtime.schedule_operation(&spawn_handler::Operation::DepositReserve { amount });
+                Ok(true)
+            }
+            /// Pays `amount` reserve tokens out to `recipient_owner` on `recipient_chain_id`.
+            async fn withdraw_reserve(
+                &self,
+                amount: String,
+                recipient_chain_id: String,
+                recipient_owner: String,
+            ) -> async_graphql::Result<bool> {
+                let amount = parse_amount(&amount)?;
+                let chain_id: ChainId = recipient_chain_id
+                    .parse()
+                    .map_err(|_| format!("invalid chain id: {recipient_chain_id}"))?;
+                let owner: AccountOwner = recipient_owner
+                    .parse()
+                    .map_err(|_| format!("invalid account owner: {recipient_owner}"))?;
+                self.runtime.schedule_operation(&spawn_handler::Operation::WithdrawReserve {
+                    amount,
+                    recipient: Account { chain_id, owner },
+                });
+                Ok(true)
+            }
+            async fn process_pending_spawns(&self) -> bool {
+                self.runtime.schedule_operation(&spawn_handler::Operation::ProcessPendingSpawns);
+                true
+            }
+        }
+
+        let schema = Schema::build(
+            QueryRoot {
+                data: self.data(),
+                parameters: self.runtime.application_parameters(),
+                now: self.runtime.system_time(),
+            },
+            MutationRoot { runtime: self.runtime.clone() },
+            EmptySubscription,
+        )
+        .finish();
+
+        schema.execute(request).await
+    }
+}
+
+service!(SpawnHandlerService);
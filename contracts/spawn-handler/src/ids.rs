@@ -0,0 +1,80 @@
+//! Deterministic spawn ids.
+//!
+//! A spawn id is a hash of what caused the spawn, so a redelivered or repeated
+//! resolution yields the same id and the handler and the factory can refuse
+//! the duplicate.
+
+use linera_sdk::base::Timestamp;
+use sha3::{Digest, Sha3_256};
+
+/// Id of the spawn `rule_id` at `rule_version` makes when `parent_market_id`
+/// resolves to `winning_outcome`.
+pub fn spawn_id(
+    parent_market_id: &str,
+    winning_outcome: &str,
+    rule_id: &str,
+    rule_version: u32,
+) -> String {
+    hash_id(
+        b"resolution",
+        &[
+            parent_market_id.as_bytes(),
+            winning_outcome.as_bytes(),
+            rule_id.as_bytes(),
+            &rule_version.to_le_bytes(),
+        ],
+    )
+}
+
+/// Id of the spawn a scheduled rule makes on the tick at `tick`.
+pub fn scheduled_spawn_id(rule_id: &str, rule_version: u32, tick: Timestamp) -> String {
+    hash_id(
+        b"schedule",
+        &[
+            rule_id.as_bytes(),
+            &rule_version.to_le_bytes(),
+            &tick.micros().to_le_bytes(),
+        ],
+    )
+}
+
+fn hash_id(kind: &[u8], parts: &[&[u8]]) -> String {
+    let mut hasher = Sha3_256::new();
+    hasher.update(kind);
+    // Length prefixes keep ("ab", "c") and ("a", "bc") apart.
+    for part in parts {
+        hasher.update((part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+    let digest = hasher.finalize();
+    let hex = digest.iter().map(|byte| format!("{byte:02x}")).collect::<String>();
+    format!("spawn_{hex}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_are_stable() {
+        // Pinned, since a change would let redelivered resolutions spawn again.
+        assert_eq!(
+            spawn_id("m1", "Yes", "r1", 1),
+            "spawn_788d37bc19b62867acc666afc74d7e41ae6bfe10216b2f4b84d304ac54eb9a5f"
+        );
+        assert_eq!(
+            scheduled_spawn_id("r1", 1, Timestamp::from(60_000_000)),
+            "spawn_1829981b74c4f788dc7c4bac4685c42378d0a7cd18c4a4ff1242b3768fe70db4"
+        );
+        assert_eq!(spawn_id("m1", "Yes", "r1", 1), spawn_id("m1", "Yes", "r1", 1));
+        assert_ne!(spawn_id("m1", "Yes", "r1", 1), spawn_id("m1", "Yes", "r1", 2));
+        assert_ne!(spawn_id("m1", "Yes", "r1", 1), spawn_id("m1", "No", "r1", 1));
+    }
+
+    #[test]
+    fn length_prefixes_keep_parts_apart() {
+        assert_ne!(spawn_id("ab", "c", "r1", 1), spawn_id("a", "bc", "r1", 1));
+        assert_ne!(spawn_id("m1", "ab", "c", 1), spawn_id("m1", "a", "bc", 1));
+        assert_ne!(hash_id(b"kind", &[b"ab", b"c"]), hash_id(b"kind", &[b"a", b"bc"]));
+    }
+}
@@ -1,8 +1,12 @@
 #![recursion_limit = "1024"]
 
+use fractal_abi::{
+    admin::{AdminError, AdminRole},
+    factory::{self, FactoryAbi, RoyaltyShare},
+};
 use linera_sdk::{
     abi::WithContractAbi,
-    base::{Amount, ChainId, Timestamp},
+    base::{Account, AccountOwner, Amount, ApplicationId, TimeDelta, Timestamp},
     contract::ContractRuntime,
     views::{RegisterView, View},
     Contract,
@@ -11,90 +15,36 @@ use linera_sdk::{
 use serde::{Deserialize, Serialize};
 use linera_views::{batch::Batch, store::WritableKeyValueStore};
 use linera_views::context::Context;
-use std::collections::HashMap;
-use thiserror::Error;
-
-// ABI and parameters for the Spawn Handler (SDK 0.15)
-pub mod spawn_handler {
-    use super::*;
-    use linera_sdk::abi::ContractAbi;
-
-    #[derive(Debug, Deserialize, Serialize)]
-    pub enum Operation {
-        Initialize { admin: ChainId },
-        CreateSpawnRule {
-            rule_id: String,
-            trigger_condition: TriggerCondition,
-            spawn_template: SpawnTemplate,
-        },
-        UpdateSpawnRule { rule_id: String, active: bool },
-        ProcessPendingSpawns,
-    }
 
-    #[derive(Debug, Clone, Deserialize, Serialize)]
-    pub struct ResponseBytes(pub Vec<u8>);
+pub use spawn_handler_abi::*;
 
-    #[derive(Debug, Clone, Deserialize, Serialize)]
-    pub struct Parameters;
-
-    #[derive(Debug)]
-    pub struct SpawnHandlerAbi;
-
-    impl ContractAbi for SpawnHandlerAbi {
-        type Operation = Operation;
-        type Response = ResponseBytes;
-    }
-}
+pub mod ids;
 
-/// Root state stored as a single register to avoid custom View macros
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
-pub struct SpawnHandlerStateData {
-    pub spawn_rules: HashMap<String, SpawnRule>,
-    pub pending_spawns: Vec<PendingSpawn>,
-    pub admin: Option<ChainId>,
-}
+use evaluation::{Evaluator, RuleMatch};
+use pattern::PatternCache;
+use rule_pack::RulePack;
 
 type SpawnHandlerState = RegisterView<SpawnHandlerStateData>;
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct SpawnRule {
-    pub rule_id: String,
-    pub trigger_condition: TriggerCondition,
-    pub spawn_template: SpawnTemplate,
-    pub active: bool,
-    pub created_by: ChainId,
-}
-
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub enum TriggerCondition {
-    MarketResolution {
-        market_pattern: String, // Regex pattern for market questions
-        outcome_pattern: String, // Regex pattern for outcomes
-    },
-    TimeDelay {
-        delay_seconds: u64,
-    },
-    CustomLogic {
-        logic_hash: String, // Hash of custom logic code
-    },
-}
-
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct SpawnTemplate {
-    pub question_template: String, // Template with placeholders like {parent_question}, {outcome}
-    pub outcomes: Vec<String>,
-    pub expiry_offset_seconds: u64,
-    pub seed_liquidity_ratio: f64, // Ratio of parent market's total stake
+/// Status a spawn generated by `rule` is queued with.
+fn initial_status(rule: &SpawnRule) -> SpawnStatus {
+    if rule.settings.requires_approval {
+        SpawnStatus::AwaitingApproval
+    } else {
+        SpawnStatus::Queued
+    }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct PendingSpawn {
-    pub spawn_id: String,
-    pub parent_market_id: String,
-    pub parent_outcome: String,
-    pub spawn_template: SpawnTemplate,
-    pub scheduled_time: Timestamp,
-    pub processed: bool,
+/// Roots of every `ParentInSubtree` in `condition`, however deeply nested.
+fn subtree_roots(condition: &TriggerCondition) -> Vec<&str> {
+    match condition {
+        TriggerCondition::ParentInSubtree { root_market_id } => vec![root_market_id.as_str()],
+        TriggerCondition::All { conditions } | TriggerCondition::Any { conditions } => {
+            conditions.iter().flat_map(subtree_roots).collect()
+        }
+        TriggerCondition::Not { condition } => subtree_roots(condition),
+        _ => Vec::new(),
+    }
 }
 
 use spawn_handler::Operation;
@@ -102,114 +52,369 @@ use spawn_handler::Operation;
 /// Messages for cross-chain communication
 #[derive(Debug, Deserialize, Serialize)]
 pub enum Message {
+    /// Sent by the factory chain's instance once a registered market resolves.
     MarketResolved {
-        market_id: String,
-        question: String,
-        winning_outcome: String,
-        total_stake: Amount,
+        resolution: Resolution,
     },
     SpawnMarket {
-        parent_market_id: String,
+        spawn_id: String,
+        creator: Account,
+        parent_market_id: Option<String>,
         question: String,
         outcomes: Vec<String>,
         expiry_time: Timestamp,
         seed_liquidity: Amount,
+        trading_fee_basis_points: u32,
+        royalties: Vec<RoyaltyShare>,
+    },
+    /// Sent back by the factory chain once the market for a spawn exists.
+    SpawnConfirmed {
+        spawn_id: String,
+        market_id: String,
+        application_id: ApplicationId, // Market application receiving the seed
     },
-}
-
-/// Spawn handler errors
-#[derive(Debug, Error)]
-pub enum SpawnHandlerError {
-    #[error("Unauthorized")]
-    Unauthorized,
-    #[error("Rule not found")]
-    RuleNotFound,
-    #[error("Invalid template")]
-    InvalidTemplate,
-    #[error("Processing failed")]
-    ProcessingFailed,
 }
 
 /// Spawn handler contract implementation (SDK 0.15)
 pub struct SpawnHandlerContract {
     state: SpawnHandlerState,
     runtime: ContractRuntime<Self>,
+    patterns: PatternCache,
 }
 
 impl Contract for SpawnHandlerContract {
     type Message = Message;
     type Parameters = spawn_handler::Parameters;
-    type InstantiationArgument = spawn_handler::Parameters;
+    type InstantiationArgument = spawn_handler::InstantiationArgument;
     type EventValue = ();
 
     async fn load(runtime: ContractRuntime<Self>) -> Self {
         let state = <SpawnHandlerState as View>::load(runtime.root_view_storage_context())
             .await
             .expect("Failed to load state");
-        SpawnHandlerContract { state, runtime }
+        SpawnHandlerContract {
+            state,
+            runtime,
+            patterns: PatternCache::default(),
+        }
     }
 
-    async fn instantiate(&mut self, _argument: Self::InstantiationArgument) {}
+    /// Rules given at instantiation are installed without a bond.
+    async fn instantiate(&mut self, argument: Self::InstantiationArgument) {
+        let pack = RulePack {
+            rules: argument.rules,
+        };
+        if let Err(error) = pack.validate() {
+            panic!("{error}");
+        }
+        let mut data = self.state.get().clone();
+        data.admin.instantiated_by = self.runtime.authenticated_signer();
+        if let Some(admin) = argument.admin {
+            if let Err(error) = data.admin.initialize(admin) {
+                panic!("{error}");
+            }
+        }
+        // Rules are owned by the signer or, when instantiated unsigned, by the first admin.
+        let owner = self.runtime.authenticated_signer().or_else(|| {
+            let admin = data.admin.current.as_ref();
+            admin.and_then(|admin| admin.owners.first().copied())
+        });
+        for definition in pack.rules {
+            let Some(owner) = owner else {
+                panic!("Rules installed at instantiation need a signer or an admin to own them");
+            };
+            let rule = definition.into_rule(owner, 1);
+            self.install_rule(&mut data, RuleChangeKind::Created, rule);
+        }
+        self.state.set(data);
+    }
 
     async fn execute_operation(&mut self, operation: Operation) -> spawn_handler::ResponseBytes {
         match operation {
             Operation::Initialize { admin } => {
-                if self.state.get().admin.is_some() {
-                    // already initialized; ignore
-                } else {
-                    let mut data = self.state.get().clone();
-                    data.admin = Some(admin);
-                    self.state.set(data);
-                    // create default spawn rules
-                    let creator = self.runtime.chain_id();
-                    self.create_default_rules(creator).await;
-                }
+                self.update_admin(|role, signer| role.initialize_as(signer, admin))
             }
+            Operation::TransferAdmin { new_admin } => {
+                self.update_admin(|role, signer| role.propose(signer, new_admin))
+            }
+            Operation::AcceptAdmin => self.update_admin(|role, signer| role.accept(signer)),
+            Operation::CancelAdminTransfer => self.update_admin(|role, signer| role.cancel(signer)),
             Operation::CreateSpawnRule {
                 rule_id,
                 trigger_condition,
                 spawn_template,
+                outcome_templates,
+                skip_unmatched_outcomes,
+                settings,
+                scope,
             } => {
                 let rule = SpawnRule {
                     rule_id: rule_id.clone(),
                     trigger_condition,
                     spawn_template,
+                    outcome_templates,
+                    skip_unmatched_outcomes,
+                    settings,
+                    scope,
                     active: true,
-                    created_by: self.runtime.chain_id(),
+                    created_by: self.signer(),
+                    version: 1,
                 };
                 let mut data = self.state.get().clone();
-                data.spawn_rules.insert(rule_id, rule);
+                if data.spawn_rules.contains_key(&rule_id) {
+                    panic!("Rejected spawn rule {rule_id}: {}", SpawnHandlerError::RuleAlreadyExists);
+                }
+                self.admit_new_rule(&mut data, &rule_id);
+                self.install_rule(&mut data, RuleChangeKind::Created, rule);
                 self.state.set(data);
             }
             Operation::UpdateSpawnRule { rule_id, active } => {
                 let mut data = self.state.get().clone();
-                if let Some(rule) = data.spawn_rules.get_mut(&rule_id) {
-                    if rule.created_by != self.runtime.chain_id()
-                        && data.admin != Some(self.runtime.chain_id())
-                    {
+                if let Some(rule) = data.spawn_rules.get(&rule_id).cloned() {
+                    let signer = self.runtime.authenticated_signer();
+                    if signer != Some(rule.created_by) && !self.signer_is_admin(&data) {
                         // unauthorized; ignore
-                    } else {
-                        rule.active = active;
+                    } else if rule.active != active {
+                        let rule = SpawnRule { active, ..rule };
+                        let change = if active {
+                            RuleChangeKind::Activated
+                        } else {
+                            RuleChangeKind::Deactivated
+                        };
+                        self.record_change(&mut data, change, &rule);
+                        data.spawn_rules.insert(rule_id, rule);
                         self.state.set(data);
                     }
                 }
             }
-            Operation::ProcessPendingSpawns => {
-                // minimal: mark due spawns as processed without dispatch
-                let current_time = self.runtime.system_time();
+            Operation::EditSpawnRule {
+                rule_id,
+                trigger_condition,
+                spawn_template,
+                outcome_templates,
+                skip_unmatched_outcomes,
+                settings,
+                scope,
+            } => {
                 let mut data = self.state.get().clone();
-                for p in data.pending_spawns.iter_mut() {
-                    if !p.processed && current_time >= p.scheduled_time {
-                        p.processed = true;
-                    }
+                let mut rule = self.owned_rule(&data, &rule_id);
+                if let Some(trigger_condition) = trigger_condition {
+                    rule.trigger_condition = trigger_condition;
+                }
+                if let Some(spawn_template) = spawn_template {
+                    rule.spawn_template = spawn_template;
+                }
+                if let Some(outcome_templates) = outcome_templates {
+                    rule.outcome_templates = outcome_templates;
                 }
+                if let Some(skip_unmatched_outcomes) = skip_unmatched_outcomes {
+                    rule.skip_unmatched_outcomes = skip_unmatched_outcomes;
+                }
+                if let Some(settings) = settings {
+                    rule.settings = settings;
+                }
+                let rescoped = scope.is_some();
+                if let Some(scope) = scope {
+                    rule.scope = scope;
+                }
+                rule.version = rule.version.saturating_add(1);
+                let checked = rule
+                    .validate()
+                    .and_then(|()| if rescoped { self.check_scope(&data, &rule) } else { Ok(()) });
+                if let Err(error) = checked {
+                    panic!("Rejected spawn rule {rule_id}: {error}");
+                }
+                self.record_change(&mut data, RuleChangeKind::Edited, &rule);
+                data.spawn_rules.insert(rule_id, rule);
                 self.state.set(data);
             }
+            Operation::DeleteSpawnRule { rule_id } => {
+                let mut data = self.state.get().clone();
+                let rule = self.owned_rule(&data, &rule_id);
+                self.record_change(&mut data, RuleChangeKind::Deleted, &rule);
+                data.spawn_rules.remove(&rule_id);
+                data.schedules.remove(&rule_id);
+                let bond = data.rule_bonds.remove(&rule_id);
+                self.state.set(data);
+                if let Some(bond) = bond {
+                    let reserve_owner = self.reserve_owner();
+                    self.runtime.transfer(reserve_owner, bond.posted_by, bond.amount);
+                }
+            }
+            Operation::SlashRuleBond { rule_id } => {
+                let mut data = self.state.get().clone();
+                self.require_admin(&data);
+                let Some(rule) = data.spawn_rules.remove(&rule_id) else {
+                    panic!("{}", SpawnHandlerError::RuleNotFound);
+                };
+                let bond = data
+                    .rule_bonds
+                    .remove(&rule_id)
+                    .map_or(Amount::ZERO, |bond| bond.amount);
+                data.liquidity_reserve = data.liquidity_reserve.saturating_add(bond);
+                data.schedules.remove(&rule_id);
+                self.record_change(&mut data, RuleChangeKind::Slashed { bond }, &rule);
+                self.state.set(data);
+            }
+            Operation::SetCreationPolicy { policy } => {
+                let mut data = self.state.get().clone();
+                self.require_admin(&data);
+                data.creation_policy = policy;
+                self.state.set(data);
+            }
+            Operation::SetCreatorAllowed { creator, allowed } => {
+                let mut data = self.state.get().clone();
+                self.require_admin(&data);
+                data.allowed_creators.retain(|owner| *owner != creator);
+                if allowed {
+                    data.allowed_creators.push(creator);
+                }
+                self.state.set(data);
+            }
+            Operation::TransferRuleOwnership { rule_id, new_owner } => {
+                let mut data = self.state.get().clone();
+                let rule = self.owned_rule(&data, &rule_id);
+                let change = RuleChangeKind::OwnershipTransferred {
+                    from: rule.created_by,
+                    to: new_owner,
+                };
+                let rule = SpawnRule {
+                    created_by: new_owner,
+                    ..rule
+                };
+                self.record_change(&mut data, change, &rule);
+                data.spawn_rules.insert(rule_id, rule);
+                self.state.set(data);
+            }
+            Operation::ProcessPendingSpawns => self.process_pending_spawns(),
+            Operation::DepositReserve { amount } => self.deposit_reserve(amount),
+            Operation::WithdrawReserve { amount, recipient } => {
+                self.withdraw_reserve(amount, recipient)
+            }
+            Operation::SetSpawnLimits { limits } => {
+                let mut data = self.state.get().clone();
+                self.require_admin(&data);
+                data.limits = limits;
+                self.state.set(data);
+            }
+            Operation::RequeueSpawn { spawn_id } => {
+                self.resolve_dead_letter(&spawn_id, &[SpawnStatus::Failed], |spawn| {
+                    spawn.status = SpawnStatus::Queued;
+                    spawn.attempts = 0;
+                    spawn.retry_at = None;
+                })
+            }
+            Operation::CancelSpawn { spawn_id } => self.resolve_dead_letter(
+                &spawn_id,
+                &[SpawnStatus::Queued, SpawnStatus::Failed],
+                |spawn| spawn.status = SpawnStatus::Cancelled,
+            ),
+            Operation::ImportRules { pack } => self.import_rules(pack),
+            Operation::ExportRules { rule_ids } => {
+                let data = self.state.get();
+                let pack = match rule_ids {
+                    None => RulePack::from_rules(data.spawn_rules.values()),
+                    Some(rule_ids) => RulePack::from_rules(rule_ids.iter().map(|rule_id| {
+                        data.spawn_rules
+                            .get(rule_id)
+                            .unwrap_or_else(|| panic!("{}", SpawnHandlerError::RuleNotFound))
+                    })),
+                };
+                let bytes = serde_json::to_vec(&pack).expect("Failed to serialize rule pack");
+                return spawn_handler::ResponseBytes(bytes);
+            }
+            Operation::ApproveSpawn {
+                spawn_id,
+                question,
+                outcomes,
+                note,
+            } => self.approve_spawn(&spawn_id, question, outcomes, note),
+            Operation::RejectSpawn { spawn_id, note } => {
+                self.review_spawn(&spawn_id, note, |spawn| {
+                    spawn.status = SpawnStatus::Rejected;
+                    ReviewDecision::Rejected
+                })
+            }
+            Operation::NotifyResolved {
+                market_id,
+                winning_outcome,
+                outcome_stakes,
+            } => self.notify_resolved(market_id, winning_outcome, outcome_stakes),
         }
         spawn_handler::ResponseBytes(Vec::new())
     }
 
-    async fn execute_message(&mut self, _message: Message) {}
+    async fn execute_message(&mut self, message: Message) {
+        match message {
+            Message::MarketResolved { resolution } => {
+                let factory_chain_id = self.runtime.application_parameters().factory_chain_id;
+                if self.runtime.message_origin_chain_id() != Some(factory_chain_id) {
+                    // only the factory chain reports resolutions; ignore
+                } else {
+                    self.queue_spawns(resolution)
+                }
+            }
+            Message::SpawnMarket {
+                spawn_id,
+                creator,
+                parent_market_id,
+                question,
+                outcomes,
+                expiry_time,
+                seed_liquidity,
+                trading_fee_basis_points,
+                royalties,
+            } => {
+                if self.runtime.message_is_bouncing() == Some(true) {
+                    let now = self.runtime.system_time();
+                    let mut data = self.state.get().clone();
+                    data.fail_dispatch(&spawn_id, "rejected by the factory chain", now);
+                    self.state.set(data);
+                } else if self.runtime.message_origin_chain_id()
+                    != Some(self.runtime.application_parameters().home_chain_id)
+                {
+                    // only the home chain requests spawns; ignore
+                } else {
+                    let operation = factory::Operation::SpawnMarket {
+                        spawn_id: spawn_id.clone(),
+                        creator,
+                        parent_market_id,
+                        question,
+                        outcomes,
+                        expiry_time,
+                        seed_liquidity,
+                        trading_fee_basis_points,
+                        royalties,
+                    };
+                    let (market_id, application_id) = self.create_spawned_market(operation);
+                    if let Some(origin) = self.runtime.message_origin_chain_id() {
+                        self.runtime
+                            .prepare_message(Message::SpawnConfirmed {
+                                spawn_id,
+                                market_id,
+                                application_id,
+                            })
+                            .send_to(origin);
+                    }
+                }
+            }
+            Message::SpawnConfirmed {
+                spawn_id,
+                market_id,
+                application_id,
+            } => {
+                let factory_chain_id = self.runtime.application_parameters().factory_chain_id;
+                if self.runtime.message_origin_chain_id() != Some(factory_chain_id) {
+                    // only the factory chain confirms spawns; ignore
+                } else {
+                    self.confirm_spawn(&spawn_id, market_id, application_id);
+                }
+            }
+        }
+        if self.runtime.application_parameters().auto_process {
+            self.process_pending_spawns();
+        }
+    }
 
     async fn store(self) {
         let mut batch = Batch::default();
@@ -225,84 +430,650 @@ impl Contract for SpawnHandlerContract {
 }
 
 impl SpawnHandlerContract {
-    async fn create_default_rules(&mut self, creator: ChainId) {
-        // Default rule for political events
-        let political_rule = SpawnRule {
-            rule_id: "political_consequences".to_string(),
-            trigger_condition: TriggerCondition::MarketResolution {
-                market_pattern: ".*election.*|.*vote.*|.*policy.*".to_string(),
-                outcome_pattern: ".*".to_string(),
-            },
-            spawn_template: SpawnTemplate {
-                question_template: "What will be the economic impact of {outcome}?".to_string(),
-                outcomes: vec![
-                    "Significant positive impact".to_string(),
-                    "Moderate positive impact".to_string(),
-                    "No significant impact".to_string(),
-                    "Moderate negative impact".to_string(),
-                    "Significant negative impact".to_string(),
-                ],
-                expiry_offset_seconds: 30 * 24 * 3600, // 30 days
-                seed_liquidity_ratio: 0.1, // 10% of parent
-            },
-            active: true,
-            created_by: creator,
+    /// Applies `update` to the admin role on behalf of the signer; panics if
+    /// it fails or the operation is unsigned.
+    fn update_admin(
+        &mut self,
+        update: impl FnOnce(&mut AdminRole, AccountOwner) -> Result<(), AdminError>,
+    ) {
+        let Some(signer) = self.runtime.authenticated_signer() else {
+            panic!("{}", SpawnHandlerError::Unauthorized);
         };
+        let mut data = self.state.get().clone();
+        if let Err(error) = update(&mut data.admin, signer) {
+            panic!("{error}");
+        }
+        self.state.set(data);
+    }
 
-        // Default rule for sports events
-        let sports_rule = SpawnRule {
-            rule_id: "sports_aftermath".to_string(),
-            trigger_condition: TriggerCondition::MarketResolution {
-                market_pattern: ".*championship.*|.*tournament.*|.*match.*".to_string(),
-                outcome_pattern: ".*".to_string(),
-            },
-            spawn_template: SpawnTemplate {
-                question_template: "How will {outcome} affect team performance next season?".to_string(),
-                outcomes: vec![
-                    "Significantly better".to_string(),
-                    "Slightly better".to_string(),
-                    "No change".to_string(),
-                    "Slightly worse".to_string(),
-                    "Significantly worse".to_string(),
-                ],
-                expiry_offset_seconds: 90 * 24 * 3600, // 90 days
-                seed_liquidity_ratio: 0.05, // 5% of parent
-            },
-            active: true,
-            created_by: creator,
+    /// Signer of the current operation; panics if it is unsigned.
+    fn signer(&mut self) -> AccountOwner {
+        let Some(signer) = self.runtime.authenticated_signer() else {
+            panic!("{}", SpawnHandlerError::Unauthorized);
         };
+        signer
+    }
 
+    /// Whether the signer of the current operation holds the admin role.
+    fn signer_is_admin(&mut self, data: &SpawnHandlerStateData) -> bool {
+        self.runtime
+            .authenticated_signer()
+            .is_some_and(|signer| data.admin.is_admin(signer))
+    }
+
+    /// Panics unless the signer of the current operation holds the admin role.
+    fn require_admin(&mut self, data: &SpawnHandlerStateData) {
+        if !self.signer_is_admin(data) {
+            panic!("{}", SpawnHandlerError::Unauthorized);
+        }
+    }
+
+    /// Returns a rule about to be changed, which only its owner or the admin may do.
+    fn owned_rule(&mut self, data: &SpawnHandlerStateData, rule_id: &str) -> SpawnRule {
+        let Some(rule) = data.spawn_rules.get(rule_id) else {
+            panic!("{}", SpawnHandlerError::RuleNotFound);
+        };
+        let signer = self.runtime.authenticated_signer();
+        if signer != Some(rule.created_by) && !self.signer_is_admin(data) {
+            panic!("{}", SpawnHandlerError::Unauthorized);
+        }
+        rule.clone()
+    }
+
+    /// Enforces the creation policy for a new rule owned by the signer, and
+    /// takes the bond from them. The admin is exempt.
+    fn admit_new_rule(&mut self, data: &mut SpawnHandlerStateData, rule_id: &str) {
+        let creator = self.signer();
+        if data.admin.is_admin(creator) {
+            return;
+        }
+        let policy = data.creation_policy.clone();
+        if policy.allow_list_only && !data.allowed_creators.contains(&creator) {
+            panic!("Rejected spawn rule {rule_id}: {}", SpawnHandlerError::Unauthorized);
+        }
+        let owned = data
+            .spawn_rules
+            .values()
+            .filter(|rule| rule.created_by == creator)
+            .count();
+        if policy
+            .max_rules_per_creator
+            .is_some_and(|max| owned >= max as usize)
+        {
+            panic!("Rejected spawn rule {rule_id}: {}", SpawnHandlerError::RuleLimitReached);
+        }
+        if policy.bond > Amount::ZERO {
+            let chain_id = self.runtime.chain_id();
+            let bond_account = Account {
+                chain_id,
+                owner: self.reserve_owner(),
+            };
+            self.runtime.transfer(creator, bond_account, policy.bond);
+            data.rule_bonds.insert(
+                rule_id.to_string(),
+                RuleBond {
+                    amount: policy.bond,
+                    posted_by: Account {
+                        chain_id,
+                        owner: creator,
+                    },
+                },
+            );
+        }
+    }
+
+    /// Validates `rule` and stores it, recording `change` in the history.
+    fn install_rule(
+        &mut self,
+        data: &mut SpawnHandlerStateData,
+        change: RuleChangeKind,
+        rule: SpawnRule,
+    ) {
+        if let Err(error) = rule.validate().and_then(|()| self.check_scope(data, &rule)) {
+            panic!("Rejected spawn rule {}: {error}", rule.rule_id);
+        }
+        self.record_change(data, change, &rule);
+        data.spawn_rules.insert(rule.rule_id.clone(), rule);
+    }
+
+    /// Installs every rule of `pack`, or none if one of them is rejected. New
+    /// rules are owned by the caller; existing ones keep their owner and get a
+    /// new version.
+    fn import_rules(&mut self, pack: RulePack) {
+        if let Err(error) = pack.validate() {
+            panic!("{error}");
+        }
         let mut data = self.state.get().clone();
-        data.spawn_rules.insert("political_consequences".to_string(), political_rule);
-        data.spawn_rules.insert("sports_aftermath".to_string(), sports_rule);
+        for definition in pack.rules {
+            let (change, rule) = if data.spawn_rules.contains_key(&definition.rule_id) {
+                let existing = self.owned_rule(&data, &definition.rule_id);
+                let version = existing.version.saturating_add(1);
+                (RuleChangeKind::Edited, definition.into_rule(existing.created_by, version))
+            } else {
+                self.admit_new_rule(&mut data, &definition.rule_id);
+                (RuleChangeKind::Created, definition.into_rule(self.signer(), 1))
+            };
+            self.install_rule(&mut data, change, rule);
+        }
         self.state.set(data);
     }
 
-    fn matches_trigger_condition(
-        &self,
-        condition: &TriggerCondition,
-        question: &str,
-        outcome: &str,
-    ) -> bool {
-        match condition {
-            TriggerCondition::MarketResolution {
-                market_pattern,
-                outcome_pattern,
-            } => {
-                // Simple pattern matching (in production, use regex crate)
-                question.to_lowercase().contains(&market_pattern.to_lowercase())
-                    && outcome.to_lowercase().contains(&outcome_pattern.to_lowercase())
+    fn record_change(
+        &mut self,
+        data: &mut SpawnHandlerStateData,
+        change: RuleChangeKind,
+        rule: &SpawnRule,
+    ) {
+        data.rule_history.push(RuleChange {
+            rule_id: rule.rule_id.clone(),
+            version: rule.version,
+            change,
+            // Unsigned only at instantiation, where rules are installed for their owner
+            changed_by: self.runtime.authenticated_signer().unwrap_or(rule.created_by),
+            changed_at: self.runtime.system_time(),
+            rule: rule.clone(),
+        });
+    }
+
+    /// Queues a spawn for every active rule whose trigger matches the resolution,
+    /// highest priority first, until an exclusive rule matches. Rules over
+    /// their caps still match but queue nothing, and seeds are cut down to what
+    /// is left of the resolution's seed cap.
+    fn queue_spawns(&mut self, resolution: Resolution) {
+        let mut data = self.state.get().clone();
+        let now = self.runtime.system_time();
+        let chain_id = self.runtime.chain_id();
+        data.counters.roll_over(day_index(now));
+        let admin = data.admin.clone();
+        let mut evaluator = Evaluator {
+            patterns: &mut self.patterns,
+            now,
+            admin: &admin,
+        };
+        for RuleMatch { rule, spawn } in evaluator.matching_rules(&data.spawn_rules, &resolution) {
+            let Some(spawn) = spawn else {
+                continue;
+            };
+            let spawn_id = ids::spawn_id(
+                &resolution.market_id,
+                &resolution.winning_outcome,
+                &rule.rule_id,
+                rule.version,
+            );
+            if data.spawn_ids.contains(&spawn_id) {
+                // already queued by an earlier delivery of this resolution
+                continue;
+            }
+            if !data.counters.allows(&data.limits, &rule, &resolution) {
+                continue;
+            }
+            let seed_allowance = data.counters.seed_allowance(&data.limits, &resolution.market_id);
+            let seed_liquidity = spawn.seed_liquidity.min(seed_allowance);
+            let author = Account {
+                chain_id: rule.settings.royalties.payout_chain_id.unwrap_or(chain_id),
+                owner: rule.created_by,
+            };
+            data.spawn_ids.insert(spawn_id.clone());
+            data.pending_spawns.push(PendingSpawn {
+                spawn_id,
+                rule_id: rule.rule_id.clone(),
+                parent_market_id: Some(resolution.market_id.clone()),
+                parent_outcome: resolution.winning_outcome.clone(),
+                parent_total_stake: resolution.total_stake,
+                question: spawn.question,
+                outcomes: spawn.outcomes,
+                seed_liquidity,
+                creator: author,
+                trading_fee_basis_points: rule.settings.royalties.trading_fee_basis_points,
+                royalties: rule.settings.royalties.shares(author),
+                spawn_template: spawn.spawn_template,
+                scheduled_time: spawn.scheduled_time,
+                status: initial_status(&rule),
+                attempts: 0,
+                last_dispatched: None,
+                retry_at: None,
+                seed_escrowed: false,
+                market_id: None,
+                review: None,
+            });
+            data.spawn_count = data.spawn_count.saturating_add(1);
+            data.counters.record(&rule.rule_id, &resolution.market_id, seed_liquidity);
+        }
+        self.state.set(data);
+    }
+
+    /// Sends a `SpawnMarket` message to the factory chain for every queued spawn
+    /// whose scheduled time and retry time have passed, earliest first, and
+    /// escrows its seed liquidity out of the reserve on the first attempt; the
+    /// seed is paid to the market once confirmed. Later spawns, and spawns the
+    /// reserve cannot cover yet, stay queued; dispatched spawns left
+    /// unconfirmed for too long are retried.
+    fn process_pending_spawns(&mut self) {
+        let current_time = self.runtime.system_time();
+        self.queue_scheduled_spawns(current_time);
+        let factory_chain_id = self.runtime.application_parameters().factory_chain_id;
+        let mut data = self.state.get().clone();
+        let timeout = TimeDelta::from_secs(CONFIRMATION_TIMEOUT_SECONDS);
+        let unconfirmed = data
+            .pending_spawns
+            .iter()
+            .filter(|spawn| spawn.status == SpawnStatus::Dispatched)
+            .filter(|spawn| {
+                spawn
+                    .last_dispatched
+                    .is_some_and(|dispatched| dispatched.saturating_add(timeout) <= current_time)
+            })
+            .map(|spawn| spawn.spawn_id.clone())
+            .collect::<Vec<_>>();
+        for spawn_id in unconfirmed {
+            data.fail_dispatch(&spawn_id, "not confirmed in time", current_time);
+        }
+        let mut due = data
+            .pending_spawns
+            .iter()
+            .enumerate()
+            .filter(|(_, spawn)| {
+                spawn.status == SpawnStatus::Queued
+                    && spawn.scheduled_time <= current_time
+                    && spawn.retry_at.is_none_or(|retry_at| retry_at <= current_time)
+            })
+            .map(|(index, spawn)| (spawn.scheduled_time, index))
+            .collect::<Vec<_>>();
+        due.sort();
+        for (_, index) in due {
+            let spawn = &mut data.pending_spawns[index];
+            if !spawn.seed_escrowed {
+                let Ok(remaining) = data.liquidity_reserve.try_sub(spawn.seed_liquidity) else {
+                    continue;
+                };
+                data.liquidity_reserve = remaining;
+                spawn.seed_escrowed = true;
+            }
+            let message = Message::SpawnMarket {
+                spawn_id: spawn.spawn_id.clone(),
+                creator: spawn.creator,
+                parent_market_id: spawn.parent_market_id.clone(),
+                question: spawn.question.clone(),
+                outcomes: spawn.outcomes.clone(),
+                expiry_time: evaluation::expiry_time(&spawn.spawn_template, spawn.scheduled_time),
+                seed_liquidity: spawn.seed_liquidity,
+                trading_fee_basis_points: spawn.trading_fee_basis_points,
+                royalties: spawn.royalties.clone(),
+            };
+            self.runtime
+                .prepare_message(message)
+                .with_tracking()
+                .send_to(factory_chain_id);
+            spawn.status = SpawnStatus::Dispatched;
+            spawn.attempts = spawn.attempts.saturating_add(1);
+            spawn.last_dispatched = Some(current_time);
+            spawn.retry_at = None;
+        }
+        self.state.set(data);
+    }
+
+    /// Queues a spawn for every active scheduled rule with a tick since it last
+    /// fired. Missed ticks are not replayed: only the latest one fires, and a
+    /// tick blocked by the daily caps is skipped.
+    fn queue_scheduled_spawns(&mut self, now: Timestamp) {
+        let chain_id = self.runtime.chain_id();
+        let mut data = self.state.get().clone();
+        data.counters.roll_over(day_index(now));
+        let admin = data.admin.clone();
+        let mut evaluator = Evaluator {
+            patterns: &mut self.patterns,
+            now,
+            admin: &admin,
+        };
+        // Sorted so every validator queues spawns in the same order.
+        let mut rule_ids = data.spawn_rules.keys().cloned().collect::<Vec<_>>();
+        rule_ids.sort();
+        for rule_id in rule_ids {
+            let rule = data.spawn_rules[&rule_id].clone();
+            let TriggerCondition::Schedule {
+                recurrence,
+                start_time,
+                end_time,
+                max_occurrences,
+            } = &rule.trigger_condition
+            else {
+                continue;
+            };
+            let schedule = data.schedules.entry(rule_id.clone()).or_default();
+            if !rule.active || max_occurrences.is_some_and(|max| schedule.occurrences >= max) {
+                continue;
+            }
+            let until = end_time.map_or(now, |end_time| end_time.min(now));
+            let Some(tick) = recurrence.latest_tick(*start_time, until) else {
+                continue;
+            };
+            if schedule.last_fired.is_some_and(|last_fired| last_fired >= tick) {
+                continue;
+            }
+            schedule.last_fired = Some(tick);
+            if !data.counters.allows_today(&data.limits, &rule) {
+                continue;
+            }
+            let Some(spawn) = evaluator.plan(&rule, &Resolution::scheduled(tick), 0) else {
+                continue;
+            };
+            schedule.occurrences = schedule.occurrences.saturating_add(1);
+            let author = Account {
+                chain_id: rule.settings.royalties.payout_chain_id.unwrap_or(chain_id),
+                owner: rule.created_by,
+            };
+            let spawn_id = ids::scheduled_spawn_id(&rule_id, rule.version, tick);
+            data.spawn_ids.insert(spawn_id.clone());
+            data.pending_spawns.push(PendingSpawn {
+                spawn_id,
+                rule_id,
+                parent_market_id: None,
+                parent_outcome: String::new(),
+                parent_total_stake: Amount::ZERO,
+                question: spawn.question,
+                outcomes: spawn.outcomes,
+                seed_liquidity: spawn.seed_liquidity,
+                creator: author,
+                trading_fee_basis_points: rule.settings.royalties.trading_fee_basis_points,
+                royalties: rule.settings.royalties.shares(author),
+                spawn_template: spawn.spawn_template,
+                scheduled_time: spawn.scheduled_time,
+                status: initial_status(&rule),
+                attempts: 0,
+                last_dispatched: None,
+                retry_at: None,
+                seed_escrowed: false,
+                market_id: None,
+                review: None,
+            });
+            data.spawn_count = data.spawn_count.saturating_add(1);
+            data.counters.record_today(&rule.rule_id);
+        }
+        self.state.set(data);
+    }
+
+    /// Returns the factory if it lives on this chain and can be called synchronously.
+    fn local_factory(&mut self) -> Option<ApplicationId<FactoryAbi>> {
+        let parameters = self.runtime.application_parameters();
+        (parameters.factory_chain_id == self.runtime.chain_id())
+            .then(|| parameters.factory_id.with_abi::<FactoryAbi>())
+    }
+
+    /// Runs on the factory chain: checks that the caller is the application the
+    /// factory registered for `market_id` and that the registry agrees on the
+    /// outcome, then sends the resolution, with its lineage and the creators
+    /// along it from the registry, to the home chain.
+    fn notify_resolved(
+        &mut self,
+        market_id: String,
+        winning_outcome: String,
+        outcome_stakes: Vec<(String, Amount)>,
+    ) {
+        let Some(factory_id) = self.local_factory() else {
+            panic!("{}", SpawnHandlerError::Unauthorized);
+        };
+        let caller = self.runtime.authenticated_caller_id();
+        let operation = factory::Operation::MarketDetails {
+            market_id: market_id.clone(),
+        };
+        let factory::Response::Market { market_info } =
+            self.runtime.call_application(true, factory_id, &operation)
+        else {
+            panic!("{}", SpawnHandlerError::MarketNotFound);
+        };
+        if caller.is_none() || market_info.application_id != caller {
+            panic!("{}", SpawnHandlerError::Unauthorized);
+        }
+        if market_info.winning_outcome.as_ref() != Some(&winning_outcome) {
+            panic!("Market {market_id} is not resolved to {winning_outcome}");
+        }
+        let operation = factory::Operation::MarketLineage {
+            market_id: market_id.clone(),
+        };
+        let factory::Response::Lineage { creator, ancestors } =
+            self.runtime.call_application(true, factory_id, &operation)
+        else {
+            panic!("{}", SpawnHandlerError::MarketNotFound);
+        };
+        let creators = std::iter::once((market_id.clone(), creator.owner))
+            .chain(ancestors.iter().map(|(id, creator)| (id.clone(), creator.owner)))
+            .collect();
+        let ancestors = ancestors.into_iter().map(|(id, _)| id).collect();
+        let total_stake = outcome_stakes
+            .iter()
+            .fold(Amount::ZERO, |total, (_, stake)| total.saturating_add(*stake));
+        let resolution = Resolution {
+            market_id,
+            question: market_info.question,
+            winning_outcome,
+            total_stake,
+            outcome_stakes,
+            depth: market_info.depth,
+            ancestors,
+            resolved_at: self.runtime.system_time(),
+            creators,
+        };
+        let home_chain_id = self.runtime.application_parameters().home_chain_id;
+        self.runtime
+            .prepare_message(Message::MarketResolved { resolution })
+            .send_to(home_chain_id);
+    }
+
+    /// Only rules owned by the creator of a market, or by an admin, may watch
+    /// it through their scope or `ParentInSubtree`. Evaluation enforces this
+    /// from the creators reported with each resolution; when the factory is on
+    /// this chain, rules are also checked against the registry up front.
+    fn check_scope(
+        &mut self,
+        data: &SpawnHandlerStateData,
+        rule: &SpawnRule,
+    ) -> Result<(), SpawnHandlerError> {
+        let Some(factory_id) = self.local_factory() else {
+            return Ok(());
+        };
+        let scope_root = rule.scope.as_ref().map(|scope| scope.market_id.as_str());
+        for market_id in scope_root.into_iter().chain(subtree_roots(&rule.trigger_condition)) {
+            let operation = factory::Operation::MarketLineage {
+                market_id: market_id.to_string(),
+            };
+            match self.runtime.call_application(true, factory_id, &operation) {
+                factory::Response::Lineage { creator, .. }
+                    if creator.owner == rule.created_by || data.admin.is_admin(rule.created_by) => {}
+                factory::Response::Lineage { .. } => return Err(SpawnHandlerError::Unauthorized),
+                _ => return Err(SpawnHandlerError::MarketNotFound),
             }
-            TriggerCondition::TimeDelay { .. } => true, // Always matches, delay handled elsewhere
-            TriggerCondition::CustomLogic { .. } => false, // Not implemented yet
         }
+        Ok(())
+    }
+
+    /// Account holding the liquidity reserve: the application's own.
+    fn reserve_owner(&mut self) -> AccountOwner {
+        AccountOwner::from(self.runtime.application_id().forget_abi())
+    }
+
+    fn deposit_reserve(&mut self, amount: Amount) {
+        let Some(signer) = self.runtime.authenticated_signer() else {
+            panic!("{}", SpawnHandlerError::Unauthorized);
+        };
+        let reserve = Account {
+            chain_id: self.runtime.chain_id(),
+            owner: self.reserve_owner(),
+        };
+        self.runtime.transfer(signer, reserve, amount);
+        let mut data = self.state.get().clone();
+        data.liquidity_reserve = data.liquidity_reserve.saturating_add(amount);
+        self.state.set(data);
+    }
+
+    fn withdraw_reserve(&mut self, amount: Amount, recipient: Account) {
+        let mut data = self.state.get().clone();
+        self.require_admin(&data);
+        let Ok(remaining) = data.liquidity_reserve.try_sub(amount) else {
+            panic!("{}", SpawnHandlerError::InsufficientReserve);
+        };
+        data.liquidity_reserve = remaining;
+        self.state.set(data);
+        let reserve_owner = self.reserve_owner();
+        self.runtime.transfer(reserve_owner, recipient, amount);
     }
 
-    fn process_template(&self, template: &str, market_id: &str, outcome: &str) -> String {
-        template
-            .replace("{parent_market_id}", market_id)
-            .replace("{outcome}", outcome)
-            .replace("{parent_question}", "the previous event") // Simplified
+    /// Runs on the factory chain: submits a `SpawnMarket` operation to the
+    /// factory and returns the id of the market and of its application.
+    /// Panicking rejects the message, which then bounces back to the sender.
+    fn create_spawned_market(&mut self, operation: factory::Operation) -> (String, ApplicationId) {
+        let factory_id = self
+            .runtime
+            .application_parameters()
+            .factory_id
+            .with_abi::<FactoryAbi>();
+        let market_id = match self.runtime.call_application(true, factory_id, &operation) {
+            // A retried message for a market that already exists succeeds.
+            factory::Response::MarketCreated { market_id }
+            | factory::Response::AlreadySpawned { market_id, .. } => market_id,
+            response => panic!("Factory refused spawned market: {response:?}"),
+        };
+        let operation = factory::Operation::MarketDetails { market_id };
+        match self.runtime.call_application(true, factory_id, &operation) {
+            factory::Response::Market { market_info } => match market_info.application_id {
+                Some(application_id) => (market_info.market_id, application_id),
+                None => panic!("Spawned market {} has no application", market_info.market_id),
+            },
+            response => panic!("Factory lost spawned market: {response:?}"),
+        }
+    }
+
+    /// Marks a spawn confirmed, even if it was meanwhile queued for a retry or
+    /// dead-lettered, since its market exists either way, and pays the seed to
+    /// the market's application. A spawn whose seed went back to the reserve is
+    /// paid from the reserve, if it still covers the seed. Spawns already
+    /// confirmed, cancelled or rejected are left alone.
+    fn confirm_spawn(&mut self, spawn_id: &str, market_id: String, application_id: ApplicationId) {
+        let mut data = self.state.get().clone();
+        let Some(spawn) = data.pending_spawns.iter_mut().find(|p| p.spawn_id == spawn_id) else {
+            return;
+        };
+        if matches!(
+            spawn.status,
+            SpawnStatus::Confirmed | SpawnStatus::Cancelled | SpawnStatus::Rejected
+        ) {
+            return;
+        }
+        let mut seed = spawn.release_seed();
+        if seed == Amount::ZERO {
+            if let Ok(remaining) = data.liquidity_reserve.try_sub(spawn.seed_liquidity) {
+                data.liquidity_reserve = remaining;
+                seed = spawn.seed_liquidity;
+            }
+        }
+        spawn.status = SpawnStatus::Confirmed;
+        spawn.market_id = Some(market_id);
+        spawn.retry_at = None;
+        data.dead_letters.retain(|letter| letter.spawn_id != spawn_id);
+        self.state.set(data);
+        if seed > Amount::ZERO {
+            let reserve_owner = self.reserve_owner();
+            let market_account = Account {
+                chain_id: self.runtime.application_parameters().factory_chain_id,
+                owner: AccountOwner::from(application_id),
+            };
+            self.runtime.transfer(reserve_owner, market_account, seed);
+        }
+    }
+
+    /// Releases a spawn awaiting approval, after applying the reviewer's edits.
+    fn approve_spawn(
+        &mut self,
+        spawn_id: &str,
+        question: Option<String>,
+        outcomes: Option<Vec<String>>,
+        note: Option<String>,
+    ) {
+        self.review_spawn(spawn_id, note, |spawn| {
+            let decision = if question.is_none() && outcomes.is_none() {
+                ReviewDecision::Approved
+            } else {
+                ReviewDecision::Edited {
+                    original_question: spawn.question.clone(),
+                    original_outcomes: spawn.outcomes.clone(),
+                }
+            };
+            if let Some(question) = question {
+                spawn.question = question;
+            }
+            if let Some(outcomes) = outcomes {
+                spawn.outcomes = outcomes;
+            }
+            if spawn.question.trim().is_empty() || spawn.outcomes.len() < 2 {
+                panic!(
+                    "{}",
+                    SpawnHandlerError::InvalidSpawn(
+                        "a spawned market needs a question and at least two outcomes".to_string()
+                    )
+                );
+            }
+            spawn.status = SpawnStatus::Queued;
+            decision
+        })
+    }
+
+    /// Applies `decide` to a spawn awaiting approval and records the returned
+    /// decision with the reviewing signer. Only the owner of the spawn's rule,
+    /// or the admin, may review; spawns of deleted rules are left to the admin.
+    fn review_spawn(
+        &mut self,
+        spawn_id: &str,
+        note: Option<String>,
+        decide: impl FnOnce(&mut PendingSpawn) -> ReviewDecision,
+    ) {
+        let reviewer = self.signer();
+        let now = self.runtime.system_time();
+        let mut data = self.state.get().clone();
+        let Some(index) = data.pending_spawns.iter().position(|spawn| {
+            spawn.spawn_id == spawn_id && spawn.status == SpawnStatus::AwaitingApproval
+        }) else {
+            panic!("{}", SpawnHandlerError::SpawnNotFound);
+        };
+        let owner = data
+            .spawn_rules
+            .get(&data.pending_spawns[index].rule_id)
+            .map(|rule| rule.created_by);
+        if owner != Some(reviewer) && !data.admin.is_admin(reviewer) {
+            panic!("{}", SpawnHandlerError::Unauthorized);
+        }
+        let spawn = &mut data.pending_spawns[index];
+        let decision = decide(spawn);
+        spawn.review = Some(SpawnReview {
+            decision,
+            reviewed_by: reviewer,
+            reviewed_at: now,
+            note,
+        });
+        self.state.set(data);
+    }
+
+    /// Admin only: applies `update` to a spawn in one of the `allowed` states
+    /// and removes it from the dead letters.
+    fn resolve_dead_letter(
+        &mut self,
+        spawn_id: &str,
+        allowed: &[SpawnStatus],
+        update: impl FnOnce(&mut PendingSpawn),
+    ) {
+        let mut data = self.state.get().clone();
+        self.require_admin(&data);
+        let Some(spawn) = data
+            .pending_spawns
+            .iter_mut()
+            .find(|spawn| spawn.spawn_id == spawn_id && allowed.contains(&spawn.status))
+        else {
+            panic!("{}", SpawnHandlerError::SpawnNotFound);
+        };
+        update(spawn);
+        if spawn.status == SpawnStatus::Cancelled {
+            data.liquidity_reserve = data.liquidity_reserve.saturating_add(spawn.release_seed());
+        }
+        data.dead_letters.retain(|letter| letter.spawn_id != spawn_id);
+        self.state.set(data);
     }
 }
 
@@ -311,4 +1082,17 @@ impl WithContractAbi for SpawnHandlerContract {
 }
 
 // Export the contract implementation for the Wasm module
-linera_sdk::contract!(SpawnHandlerContract);
\ No newline at end of file
+linera_sdk::contract!(SpawnHandlerContract);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_rule_pack_is_valid() {
+        let pack: RulePack = serde_json::from_str(include_str!("../rule-packs/default.json"))
+            .expect("default rule pack should parse");
+        pack.validate().expect("default rule pack should validate");
+        let rule_ids = pack.rules.iter().map(|rule| rule.rule_id.as_str()).collect::<Vec<_>>();
+        assert_eq!(rule_ids, ["political_consequences", "sports_aftermath"]);
+    }
+}